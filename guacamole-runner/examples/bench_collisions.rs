@@ -0,0 +1,115 @@
+//! Times the spatial hash against testing every pair of colliders.
+//!
+//! Run with `cargo run --release --example bench_collisions -- [count]`
+
+// The game is a binary so its collision modules are pulled in directly
+#[path = "../src/collision.rs"]
+mod collision;
+#[path = "../src/spatial.rs"]
+mod spatial;
+
+use std::time::Instant;
+
+use vermarine_lib::{
+    components::{
+        Transform,
+    },
+    rendering::{
+        Sprite,
+        draw_buffer::{
+            DrawCommand,
+        },
+    },
+    tetra::{
+        self,
+        math::{
+            Vec2,
+        },
+    },
+    shipyard::{
+        self,
+        *,
+    },
+};
+
+use rand::{
+    Rng,
+    SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+
+use collision::{
+    Collider,
+    WorldShape,
+};
+
+use spatial::{
+    rebuild_spatial_hash,
+    SpatialHash,
+};
+
+const FRAMES: u32 = 100;
+
+fn main() {
+    let count = std::env::args().nth(1).and_then(|value| value.parse().ok()).unwrap_or(10_000);
+
+    let world = World::new();
+    world.add_unique(SpatialHash::default());
+
+    // Planes sized colliders spread over a few screens
+    let mut rng = ChaCha8Rng::seed_from_u64(0);
+    for _ in 0..count {
+        world
+            .entity_builder()
+            .with(Transform::new(rng.gen_range(0., 1280. * 4.), rng.gen_range(0., 720. * 4.)))
+            .with(Sprite::from_command(
+                DrawCommand::new(0)
+                .scale(Vec2::new(2., 2.))
+                .rotation(rng.gen_range(0., std::f32::consts::PI * 2.))
+            ))
+            .with(Collider::rect(-32., -16., 64., 26.))
+            .build();
+    }
+
+    let probes: Vec<WorldShape> = (0..count)
+        .map(|_| {
+            let pos = Transform::new(rng.gen_range(0., 1280. * 4.), rng.gen_range(0., 720. * 4.));
+            Collider::rect(-20., -8., 36., 16.).world_shape(&pos, None)
+        })
+        .collect();
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        world.run(rebuild_spatial_hash);
+    }
+    let rebuild = start.elapsed() / FRAMES;
+
+    let start = Instant::now();
+    let hash_hits = world.run(|hash: UniqueView<SpatialHash>| {
+        probes.iter().map(|probe| hash.query(probe).len()).sum::<usize>()
+    });
+    let hash_query = start.elapsed();
+
+    // What the hash replaces, every probe against every collider
+    let shapes: Vec<WorldShape> = world.run(|transforms: View<Transform>, colliders: View<Collider>, sprites: View<Sprite>| {
+        (&transforms, &colliders, &sprites)
+            .iter()
+            .map(|(transform, collider, sprite)| collider.world_shape(transform, Some(sprite)))
+            .collect()
+    });
+
+    let start = Instant::now();
+    let brute_hits: usize = probes
+        .iter()
+        .map(|probe| shapes.iter().filter(|shape| shape.intersects(probe)).count())
+        .sum();
+    let brute_query = start.elapsed();
+
+    println!("{} colliders, {} queries", count, probes.len());
+    println!("rebuild: {:?} per frame", rebuild);
+    println!("spatial hash: {:?} ({} hits)", hash_query, hash_hits);
+    println!("every pair:   {:?} ({} hits)", brute_query, brute_hits);
+    println!("speedup: {:.1}x", brute_query.as_secs_f64() / (rebuild + hash_query).as_secs_f64());
+
+    assert_eq!(hash_hits, brute_hits, "the spatial hash missed or invented collisions");
+}
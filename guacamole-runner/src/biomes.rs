@@ -0,0 +1,341 @@
+use crate::{
+    tetra::{
+        graphics::{
+            Color,
+        },
+    },
+    consts::{
+        *,
+    },
+    hex::{
+        Axial,
+    },
+    map::{
+        HexChunk,
+    },
+    terrain::{
+        ChunkTiles,
+        MapGenerator,
+        NoiseTerrain,
+        StripDensity,
+        ValueNoise,
+    },
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use rand::{
+    Rng,
+    SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+
+/// Texture names from `consts::textures` a biome's tiles are drawn with
+pub struct BiomeTextures {
+    pub floor: &'static str,
+    pub floor_brick: &'static str,
+    pub floor_tilled: &'static str,
+    pub floor_grown: &'static str,
+    pub wall: &'static str,
+    pub wall_brick: &'static str,
+    /// Tint for the tops of untilled tiles
+    pub tint: Color,
+}
+
+static MEADOW_TEXTURES: BiomeTextures = BiomeTextures {
+    floor: textures::FLOOR,
+    floor_brick: textures::FLOOR_BRICK,
+    floor_tilled: textures::FLOOR_TILLED,
+    floor_grown: textures::FLOOR_GROWN,
+    wall: textures::WALL,
+    wall_brick: textures::WALL_BRICK,
+    tint: Color::WHITE,
+};
+
+/// Hillsides are bare rock
+static HIGHLANDS_TEXTURES: BiomeTextures = BiomeTextures {
+    floor: textures::FLOOR,
+    floor_brick: textures::FLOOR_BRICK,
+    floor_tilled: textures::FLOOR_TILLED,
+    floor_grown: textures::FLOOR_GROWN,
+    wall: textures::WALL_BRICK,
+    wall_brick: textures::WALL_BRICK,
+    tint: Color::rgb(0.85, 0.85, 0.9),
+};
+
+/// Paved streets instead of grass
+static CITY_TEXTURES: BiomeTextures = BiomeTextures {
+    floor: textures::FLOOR_BRICK,
+    floor_brick: textures::FLOOR_BRICK,
+    floor_tilled: textures::FLOOR_TILLED,
+    floor_grown: textures::FLOOR_GROWN,
+    wall: textures::WALL_BRICK,
+    wall_brick: textures::WALL_BRICK,
+    tint: Color::rgb(0.75, 0.75, 0.75),
+};
+
+static FARMLAND_TEXTURES: BiomeTextures = BiomeTextures {
+    floor: textures::FLOOR,
+    floor_brick: textures::FLOOR_BRICK,
+    floor_tilled: textures::FLOOR_TILLED,
+    floor_grown: textures::FLOOR_GROWN,
+    wall: textures::WALL,
+    wall_brick: textures::WALL_BRICK,
+    tint: Color::rgb(1.0, 0.95, 0.75),
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Biome {
+    Meadow,
+    RockyHighlands,
+    RuinedCity,
+    FlatFarmland,
+}
+
+impl Default for Biome {
+    fn default() -> Self {
+        Biome::Meadow
+    }
+}
+
+impl Biome {
+    pub const ALL: [Biome; 4] = [
+        Biome::Meadow,
+        Biome::RockyHighlands,
+        Biome::RuinedCity,
+        Biome::FlatFarmland,
+    ];
+
+    pub fn textures(self) -> &'static BiomeTextures {
+        match self {
+            Biome::Meadow => &MEADOW_TEXTURES,
+            Biome::RockyHighlands => &HIGHLANDS_TEXTURES,
+            Biome::RuinedCity => &CITY_TEXTURES,
+            Biome::FlatFarmland => &FARMLAND_TEXTURES,
+        }
+    }
+
+    pub fn generator(self) -> &'static (dyn MapGenerator + Send + Sync) {
+        match self {
+            Biome::Meadow => &Meadow,
+            Biome::RockyHighlands => &RockyHighlands,
+            Biome::RuinedCity => &RuinedCity,
+            Biome::FlatFarmland => &FlatFarmland,
+        }
+    }
+}
+
+/// Gentle hills and plenty of tilled strips, the biome every run starts in
+pub struct Meadow;
+
+impl MapGenerator for Meadow {
+    fn generate(&self, seed: u64, index: u64, height: usize, max_floor_height: u8) -> HexChunk {
+        let terrain = NoiseTerrain {
+            hill_scale: 0.06,
+            wall_width: 0.,
+            ruin_chance: 0.15,
+            strips: StripDensity { passes: 5, section_width: 10 },
+        };
+        // Hills only reach half the configured height so runs start out gentle
+        let mut tiles = terrain.generate_tiles(seed, index, height, (max_floor_height + 1) / 2);
+        tiles.set_biome(Biome::Meadow);
+        tiles.into_chunk()
+    }
+}
+
+/// Steep hills crossed by lots of walls with only a few tilled strips
+pub struct RockyHighlands;
+
+impl MapGenerator for RockyHighlands {
+    fn generate(&self, seed: u64, index: u64, height: usize, max_floor_height: u8) -> HexChunk {
+        let terrain = NoiseTerrain {
+            hill_scale: 0.15,
+            wall_width: 0.04,
+            ruin_chance: 0.,
+            strips: StripDensity { passes: 2, section_width: 15 },
+        };
+        let mut tiles = terrain.generate_tiles(seed, index, height, max_floor_height);
+        tiles.set_biome(Biome::RockyHighlands);
+        tiles.into_chunk()
+    }
+}
+
+/// Flat paving with a ruin every few columns
+pub struct RuinedCity;
+
+impl MapGenerator for RuinedCity {
+    fn generate(&self, seed: u64, index: u64, height: usize, _max_floor_height: u8) -> HexChunk {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(index);
+
+        let mut tiles = ChunkTiles::new(index, height);
+        for column in (6..CHUNK_WIDTH as i32 - 3).step_by(12) {
+            let center = Axial::new(
+                tiles.start_column + column + rng.gen_range(-2, 3),
+                rng.gen_range(0, height as i32),
+            );
+            let radius = rng.gen_range(1, 4);
+            tiles.place_ruin(&mut rng, center, radius);
+        }
+
+        tiles.till_strips(&mut rng, StripDensity { passes: 3, section_width: 12 });
+        tiles.set_biome(Biome::RuinedCity);
+        tiles.into_chunk()
+    }
+}
+
+/// Completely flat and packed with tilled strips
+pub struct FlatFarmland;
+
+impl MapGenerator for FlatFarmland {
+    fn generate(&self, seed: u64, index: u64, height: usize, _max_floor_height: u8) -> HexChunk {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(index);
+
+        let mut tiles = ChunkTiles::new(index, height);
+        tiles.till_strips(&mut rng, StripDensity { passes: 8, section_width: 8 });
+        tiles.set_biome(Biome::FlatFarmland);
+        tiles.into_chunk()
+    }
+}
+
+/// Runs through a random sequence of biomes, each lasting `leg_length` chunks,
+/// and blends each biome into the next over the first chunk of its leg
+pub struct BiomeSequencer {
+    pub leg_length: u64,
+}
+
+impl Default for BiomeSequencer {
+    fn default() -> Self {
+        Self {
+            leg_length: 4,
+        }
+    }
+}
+
+impl BiomeSequencer {
+    /// Which biome a leg uses, runs always start in a meadow
+    pub fn biome(&self, seed: u64, leg: u64) -> Biome {
+        if leg == 0 {
+            return Biome::Meadow;
+        }
+
+        // A different stream from the one chunks are generated with
+        let mut rng = ChaCha8Rng::seed_from_u64(seed ^ 0xB10E);
+        rng.set_stream(leg);
+        Biome::ALL[rng.gen_range(0, Biome::ALL.len())]
+    }
+}
+
+impl MapGenerator for BiomeSequencer {
+    fn generate(&self, seed: u64, index: u64, height: usize, max_floor_height: u8) -> HexChunk {
+        let leg = index / self.leg_length;
+        let biome = self.biome(seed, leg);
+        let chunk = biome.generator().generate(seed, index, height, max_floor_height);
+
+        let previous = if leg > 0 && index % self.leg_length == 0 {
+            self.biome(seed, leg - 1)
+        } else {
+            biome
+        };
+        if previous == biome {
+            return chunk;
+        }
+
+        // Tiles switch over to the new biome in noisy patches further across the chunk
+        let from = previous.generator().generate(seed, index, height, max_floor_height);
+        let noise = ValueNoise::new(seed ^ index);
+        let tiles = from.tiles
+            .into_iter()
+            .zip(chunk.tiles)
+            .enumerate()
+            .map(|(i, (from, to))| {
+                let (x, y) = ((i % CHUNK_WIDTH) as f32, (i / CHUNK_WIDTH) as f32);
+                let t = x / CHUNK_WIDTH as f32;
+                if noise.sample(x * 0.3, y * 0.3) * 0.5 + 0.25 < t {
+                    to
+                } else {
+                    from
+                }
+            })
+            .collect();
+
+        HexChunk {
+            tiles,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate(sequencer: &BiomeSequencer, seed: u64, indices: impl Iterator<Item = u64>) -> Vec<(u64, HexChunk)> {
+        let mut chunks: Vec<(u64, HexChunk)> = indices
+            .map(|index| (index, sequencer.generate(seed, index, HEIGHT, MAX_FLOOR_HEIGHT)))
+            .collect();
+        chunks.sort_by_key(|(index, _)| *index);
+        chunks
+    }
+
+    #[test]
+    fn seeds_pick_different_leg_orders() {
+        let sequencer = BiomeSequencer::default();
+        let legs = |seed| (0..12).map(|leg| sequencer.biome(seed, leg)).collect::<Vec<_>>();
+        let orders: Vec<Vec<Biome>> = (0..8).map(legs).collect();
+        assert!(orders.iter().any(|order| *order != orders[0]));
+
+        // Every biome turns up somewhere
+        for biome in Biome::ALL.iter() {
+            assert!(orders.iter().flatten().any(|leg| leg == biome), "{:?} never picked", biome);
+        }
+    }
+
+    #[test]
+    fn meadow_hills_follow_the_config() {
+        let grounds = |max_floor_height| (0..8)
+            .flat_map(|index| Meadow.generate(3, index, HEIGHT, max_floor_height).tiles)
+            .map(|tile| tile.ground_height)
+            .collect::<Vec<_>>();
+
+        assert!(grounds(0).iter().all(|&ground| ground == 0));
+        let tall = grounds(4);
+        assert!(tall.iter().all(|&ground| ground <= 2));
+        assert!(tall.iter().any(|&ground| ground > 0));
+    }
+
+    #[test]
+    fn biomes_tag_their_tiles() {
+        for &biome in Biome::ALL.iter() {
+            let chunk = biome.generator().generate(5, 1, HEIGHT, MAX_FLOOR_HEIGHT);
+            assert!(chunk.tiles.iter().all(|tile| tile.biome == biome), "{:?}", biome);
+        }
+        assert_ne!(Biome::RuinedCity.textures().floor, Biome::Meadow.textures().floor);
+        assert_ne!(Biome::RockyHighlands.textures().wall, Biome::Meadow.textures().wall);
+    }
+
+    #[test]
+    fn sequencer_does_not_depend_on_generation_order() {
+        let sequencer = BiomeSequencer::default();
+        let forwards = generate(&sequencer, 7, 0..16);
+        let backwards = generate(&sequencer, 7, (0..16).rev());
+        for ((_, a), (_, b)) in forwards.iter().zip(backwards.iter()) {
+            assert_eq!(a.tiles, b.tiles);
+        }
+
+        // A chunk generated on its own matches the same chunk from a full run
+        let alone = sequencer.generate(7, 9, HEIGHT, MAX_FLOOR_HEIGHT);
+        assert_eq!(alone.tiles, forwards[9].1.tiles);
+    }
+
+    #[test]
+    fn runs_start_in_a_meadow() {
+        let sequencer = BiomeSequencer::default();
+        for seed in 0..20 {
+            assert_eq!(sequencer.biome(seed, 0), Biome::Meadow);
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use crate::tetra::math::Vec2;
+
+use vermarine_lib::{
+    components::{
+        Transform,
+    },
+    rendering::{
+        Sprite,
+    },
+};
+
+/// A collider's shape in the unscaled, unrotated space of the entity's sprite
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    Circle {
+        x: f32,
+        y: f32,
+        radius: f32,
+    },
+    /// Points must be in order around a convex polygon
+    Polygon(Vec<Vec2<f32>>),
+}
+
+/// Scaled and rotated the same way as the entity's sprite so the hitbox
+/// always matches what is drawn
+#[derive(Clone, Debug)]
+pub struct Collider {
+    pub shape: Shape,
+}
+
+impl Collider {
+    pub fn rect(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            shape: Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+            },
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn circle(x: f32, y: f32, radius: f32) -> Self {
+        Self {
+            shape: Shape::Circle {
+                x,
+                y,
+                radius,
+            },
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn polygon(points: Vec<Vec2<f32>>) -> Self {
+        Self {
+            shape: Shape::Polygon(points),
+        }
+    }
+
+    /// Places the shape in the world using the entity's position and, if it has
+    /// one, its sprite's scale and rotation
+    pub fn world_shape(&self, pos: &Transform, sprite: Option<&Sprite>) -> WorldShape {
+        let (scale, rotation) = match sprite {
+            Some(sprite) => (sprite.0.scale, sprite.0.rotation),
+            None => (Vec2::one(), 0.),
+        };
+        let pos = Vec2::new(pos.x as f32, pos.y as f32);
+        let (sin, cos) = rotation.sin_cos();
+        let place = |point: Vec2<f32>| {
+            let point = point * scale;
+            Vec2::new(
+                point.x * cos - point.y * sin,
+                point.x * sin + point.y * cos,
+            ) + pos
+        };
+
+        match &self.shape {
+            Shape::Rect { x, y, width, height } => WorldShape::Polygon(vec![
+                place(Vec2::new(*x, *y)),
+                place(Vec2::new(x + width, *y)),
+                place(Vec2::new(x + width, y + height)),
+                place(Vec2::new(*x, y + height)),
+            ]),
+            Shape::Circle { x, y, radius } => WorldShape::Circle {
+                center: place(Vec2::new(*x, *y)),
+                radius: radius * scale.x.abs().max(scale.y.abs()),
+            },
+            Shape::Polygon(points) => WorldShape::Polygon(
+                points.iter().map(|point| place(*point)).collect()
+            ),
+        }
+    }
+}
+
+/// Axis aligned bounds of a `WorldShape`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec2<f32>,
+    pub max: Vec2<f32>,
+}
+
+impl Aabb {
+    /// Touching edges count as intersecting
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x &&
+        self.min.y <= other.max.y && other.min.y <= self.max.y
+    }
+}
+
+/// A collider's shape in world space
+#[derive(Clone, Debug)]
+pub enum WorldShape {
+    Polygon(Vec<Vec2<f32>>),
+    Circle {
+        center: Vec2<f32>,
+        radius: f32,
+    },
+}
+
+impl WorldShape {
+    pub fn bounds(&self) -> Aabb {
+        match self {
+            WorldShape::Polygon(points) => {
+                let mut min = Vec2::new(f32::INFINITY, f32::INFINITY);
+                let mut max = Vec2::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+                for point in points {
+                    min = Vec2::new(min.x.min(point.x), min.y.min(point.y));
+                    max = Vec2::new(max.x.max(point.x), max.y.max(point.y));
+                }
+                Aabb { min, max }
+            }
+            WorldShape::Circle { center, radius } => Aabb {
+                min: *center - Vec2::new(*radius, *radius),
+                max: *center + Vec2::new(*radius, *radius),
+            },
+        }
+    }
+
+    /// Touching shapes count as intersecting, same as the old AABB test
+    pub fn intersects(&self, other: &WorldShape) -> bool {
+        if !self.bounds().intersects(&other.bounds()) {
+            return false;
+        }
+
+        match (self, other) {
+            (WorldShape::Polygon(a), WorldShape::Polygon(b)) => polygons_intersect(a, b),
+            (WorldShape::Polygon(poly), WorldShape::Circle { center, radius }) |
+            (WorldShape::Circle { center, radius }, WorldShape::Polygon(poly)) => {
+                circle_polygon_intersect(*center, *radius, poly)
+            }
+            (WorldShape::Circle { center: a, radius: a_radius }, WorldShape::Circle { center: b, radius: b_radius }) => {
+                let delta = *a - *b;
+                let reach = a_radius + b_radius;
+                dot(delta, delta) <= reach * reach
+            }
+        }
+    }
+}
+
+fn dot(a: Vec2<f32>, b: Vec2<f32>) -> f32 {
+    a.x * b.x + a.y * b.y
+}
+
+fn project(points: &[Vec2<f32>], axis: Vec2<f32>) -> (f32, f32) {
+    points.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), point| {
+        let p = dot(*point, axis);
+        (min.min(p), max.max(p))
+    })
+}
+
+/// Normals of every edge of a convex polygon, these are the separating axes to test
+fn edge_normals(points: &[Vec2<f32>]) -> impl Iterator<Item = Vec2<f32>> + '_ {
+    (0..points.len()).filter_map(move |i| {
+        let edge = points[(i + 1) % points.len()] - points[i];
+        let normal = Vec2::new(-edge.y, edge.x);
+        let len = dot(normal, normal).sqrt();
+        if len > 0. {
+            Some(normal / len)
+        } else {
+            None
+        }
+    })
+}
+
+/// Separating axis test, the polygons overlap unless some edge normal separates them
+fn polygons_intersect(a: &[Vec2<f32>], b: &[Vec2<f32>]) -> bool {
+    edge_normals(a).chain(edge_normals(b)).all(|axis| {
+        let (a_min, a_max) = project(a, axis);
+        let (b_min, b_max) = project(b, axis);
+        a_min <= b_max && b_min <= a_max
+    })
+}
+
+fn circle_polygon_intersect(center: Vec2<f32>, radius: f32, poly: &[Vec2<f32>]) -> bool {
+    // Besides the edge normals the axis towards the closest vertex can separate a circle
+    let closest = poly.iter().copied().min_by(|a, b| {
+        let (da, db) = (*a - center, *b - center);
+        dot(da, da).partial_cmp(&dot(db, db)).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let vertex_axis = closest.and_then(|vertex| {
+        let delta = vertex - center;
+        let len = dot(delta, delta).sqrt();
+        if len > 0. {
+            Some(delta / len)
+        } else {
+            None
+        }
+    });
+
+    edge_normals(poly).chain(vertex_axis).all(|axis| {
+        let (poly_min, poly_max) = project(poly, axis);
+        let c = dot(center, axis);
+        poly_min <= c + radius && c - radius <= poly_max
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vermarine_lib::rendering::draw_buffer::DrawCommand;
+
+    fn rect_at(x: f64, y: f64, width: f32, height: f32) -> WorldShape {
+        Collider::rect(0., 0., width, height).world_shape(&Transform::new(x, y), None)
+    }
+
+    fn circle_at(x: f64, y: f64, radius: f32) -> WorldShape {
+        Collider::circle(0., 0., radius).world_shape(&Transform::new(x, y), None)
+    }
+
+    fn sprite(scale: Vec2<f32>, rotation: f32) -> Sprite {
+        Sprite::from_command(DrawCommand::new(0).scale(scale).rotation(rotation))
+    }
+
+    /// The axis aligned test colliders used before they could rotate, rects are `(x, y, width, height)`
+    fn old_aabb_intersects(a: (i32, i32, i32, i32), b: (i32, i32, i32, i32)) -> bool {
+        let (a_min, a_max) = ((a.0, a.1), (a.0 + a.2, a.1 + a.3));
+        let (b_min, b_max) = ((b.0, b.1), (b.0 + b.2, b.1 + b.3));
+        let x = (a_min.0 >= b_min.0 && a_min.0 <= b_max.0) ||
+            (a_max.0 >= b_min.0 && a_max.0 <= b_max.0) ||
+            (a_min.0 <= b_min.0 && a_max.0 >= b_max.0);
+        let y = (a_min.1 >= b_min.1 && a_min.1 <= b_max.1) ||
+            (a_max.1 >= b_min.1 && a_max.1 <= b_max.1) ||
+            (a_min.1 <= b_min.1 && a_max.1 >= b_max.1);
+        x && y
+    }
+
+    #[test]
+    fn rects_match_the_old_aabb_test_on_whole_pixels() {
+        // Touching edges and corners counted as hits before and still do
+        assert!(old_aabb_intersects((0, 0, 10, 10), (10, 0, 10, 10)));
+        assert!(old_aabb_intersects((0, 0, 10, 10), (10, 10, 10, 10)));
+        assert!(!old_aabb_intersects((0, 0, 10, 10), (11, 0, 10, 10)));
+
+        let fixed = (0, 0, 10, 10);
+        for &(width, height) in [(10, 10), (4, 4), (30, 3)].iter() {
+            for x in -32..=12 {
+                for y in -32..=12 {
+                    let moving = (x, y, width, height);
+                    let new = rect_at(x as f64, y as f64, width as f32, height as f32)
+                        .intersects(&rect_at(0., 0., 10., 10.));
+                    assert_eq!(new, old_aabb_intersects(moving, fixed), "{:?}", moving);
+                    assert_eq!(new, old_aabb_intersects(fixed, moving), "{:?}", moving);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn touching_edges_intersect() {
+        let a = rect_at(0., 0., 10., 10.);
+        let b = rect_at(10., 0., 10., 10.);
+        assert!(a.bounds().intersects(&b.bounds()));
+        assert!(a.intersects(&b));
+        assert!(b.intersects(&a));
+    }
+
+    #[test]
+    fn touching_corners_intersect() {
+        let a = rect_at(0., 0., 10., 10.);
+        let b = rect_at(10., 10., 10., 10.);
+        assert!(a.bounds().intersects(&b.bounds()));
+        assert!(a.intersects(&b));
+    }
+
+    #[test]
+    fn separated_rects_do_not_intersect() {
+        let a = rect_at(0., 0., 10., 10.);
+        let b = rect_at(10.5, 0., 10., 10.);
+        assert!(!a.bounds().intersects(&b.bounds()));
+        assert!(!a.intersects(&b));
+    }
+
+    #[test]
+    fn contained_shapes_intersect() {
+        let outer = rect_at(0., 0., 100., 100.);
+        assert!(outer.intersects(&rect_at(40., 40., 10., 10.)));
+        assert!(rect_at(40., 40., 10., 10.).intersects(&outer));
+        assert!(outer.intersects(&circle_at(50., 50., 5.)));
+        assert!(circle_at(50., 50., 5.).intersects(&outer));
+        assert!(circle_at(0., 0., 50.).intersects(&circle_at(10., 0., 5.)));
+    }
+
+    #[test]
+    fn rotated_rects_only_hit_what_they_cover() {
+        // A thin bar turned to lie along y = x, its bounds cover the whole square around it
+        let bar = Collider::rect(-10., -1., 20., 2.)
+            .world_shape(&Transform::new(0., 0.), Some(&sprite(Vec2::one(), std::f32::consts::FRAC_PI_4)));
+        let off_bar = rect_at(5., -7., 2., 2.);
+        let on_bar = rect_at(4., 4., 2., 2.);
+
+        assert!(bar.bounds().intersects(&off_bar.bounds()));
+        assert!(!bar.intersects(&off_bar));
+        assert!(bar.intersects(&on_bar));
+    }
+
+    #[test]
+    fn circle_against_polygon() {
+        let square = rect_at(0., 0., 10., 10.);
+        // Overlaps the square's bounds but stops short of its corner
+        assert!(!square.intersects(&circle_at(12., 12., 2.)));
+        assert!(square.intersects(&circle_at(11., 11., 2.)));
+        // Touching the right edge
+        assert!(square.intersects(&circle_at(12., 5., 2.)));
+        assert!(!square.intersects(&circle_at(12.5, 5., 2.)));
+    }
+
+    #[test]
+    fn colliders_scale_with_the_sprite() {
+        let collider = Collider::rect(-1., -1., 2., 2.);
+        let pos = Transform::new(0., 0.);
+        let probe = rect_at(4., 0., 1., 1.);
+
+        assert!(!collider.world_shape(&pos, None).intersects(&probe));
+        assert!(collider.world_shape(&pos, Some(&sprite(Vec2::new(5., 5.), 0.))).intersects(&probe));
+    }
+
+    #[test]
+    fn circles_take_the_larger_scale() {
+        let circle = Collider::circle(0., 0., 1.)
+            .world_shape(&Transform::new(0., 0.), Some(&sprite(Vec2::new(2., -3.), 0.)));
+        assert_eq!(circle.bounds(), Aabb {
+            min: Vec2::new(-3., -3.),
+            max: Vec2::new(3., 3.),
+        });
+    }
+}
@@ -1,74 +1,112 @@
-use vermarine_lib::components::Transform;
+use serde::{
+    Deserialize,
+    Serialize,
+};
 
 pub struct Player();
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Direction {
     Up,
     Down,
 }
 
-pub struct Plane {
-    pub direction: Direction, 
-}
+impl Direction {
+    pub fn flipped(self) -> Self {
+        match self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+        }
+    }
 
-impl Plane {
-    pub fn new(direction: Direction) -> Self {
-        Self {
-            direction,
+    /// Sign of the y velocity when flying in this direction
+    pub fn sign(self) -> f64 {
+        match self {
+            Direction::Up => -1.,
+            Direction::Down => 1.,
         }
     }
 }
 
-#[derive(Clone)]
-pub struct Collider {
-    x: i32,
-    y: i32,
-    width: u32,
-    height: u32,
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FlightPattern {
+    /// Flies diagonally towards the top or bottom of the screen
+    Diagonal(Direction),
+    /// Flies straight across the screen
+    Cruiser,
+    /// Flies across the screen bobbing around `base_y`, `period` is in frames
+    SineWave { base_y: f64, amplitude: f64, period: f64 },
+    /// Flies diagonally and turns around once it reaches `turn_y`
+    Banking { direction: Direction, turn_y: f64, turned: bool },
+    /// A bigger plane that flies diagonally at half the speed
+    Heavy(Direction),
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Plane {
+    pub pattern: FlightPattern,
+    /// Frames since the plane was spawned
+    pub age: u32,
 }
 
-impl Collider {
-    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+impl Plane {
+    pub fn new(pattern: FlightPattern) -> Self {
         Self {
-            x,
-            y,
-            width,
-            height,
+            pattern,
+            age: 0,
         }
     }
 
-    pub fn intersects(this: &Collider, this_pos: &Transform, other: &Collider, other_pos: &Transform) -> bool {
-        if  (this.xmin(this_pos) >= other.xmin(other_pos) && this.xmin(this_pos) <= other.xmax(other_pos)) ||
-            (this.xmax(this_pos) >= other.xmin(other_pos) && this.xmax(this_pos) <= other.xmax(other_pos)) || 
-            (this.xmin(this_pos) <= other.xmin(other_pos) && this.xmax(this_pos) >= other.xmax(other_pos)) {
-                if  (this.ymin(this_pos) >= other.ymin(other_pos) && this.ymin(this_pos) <= other.ymax(other_pos)) ||
-                    (this.ymax(this_pos) >= other.ymin(other_pos) && this.ymax(this_pos) <= other.ymax(other_pos)) || 
-                    (this.ymin(this_pos) <= other.ymin(other_pos) && this.ymax(this_pos) >= other.ymax(other_pos)) {
-                        return true;
-                    }
-            }
-        false
+    /// The vertical direction the plane is flying in, None for planes flying straight across
+    pub fn heading(&self) -> Option<Direction> {
+        match self.pattern {
+            FlightPattern::Diagonal(direction) => Some(direction),
+            FlightPattern::Heavy(direction) => Some(direction),
+            FlightPattern::Banking { direction, .. } => Some(direction),
+            FlightPattern::Cruiser | FlightPattern::SineWave { .. } => None,
+        }
     }
+}
 
-    pub fn xmin(&self, pos: &Transform) -> i32 {
-        self.x + pos.x as i32
-    }
+#[derive(Debug)]
+pub struct Height(pub f32);
 
-    pub fn xmax(&self, pos: &Transform) -> i32 {
-        self.x + self.width as i32 + pos.x as i32
-    }
+/// What the player landed on when their height ran out
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DeathCause {
+    Grass,
+    /// A tile whose stone wall is taller than its ground
+    Wall,
+    /// Tilled ground after the run's one bounce was already used
+    Tilled,
+}
 
-    pub fn ymin(&self, pos: &Transform) -> i32 {
-        self.y + pos.y as i32
+impl DeathCause {
+    pub fn message(self) -> &'static str {
+        match self {
+            DeathCause::Grass => "You landed in the grass",
+            DeathCause::Wall => "You crashed into a stone wall",
+            DeathCause::Tilled => "You landed on tilled soil with no bounce left",
+        }
     }
+}
 
-    pub fn ymax(&self, pos: &Transform) -> i32 {
-        self.y + self.height as i32 + pos.y as i32
-    }
+/// How a run ended
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunOutcome {
+    Died(DeathCause),
+    /// The player got past a level's finish line
+    Finished,
 }
 
-#[derive(Debug)]
-pub struct Height(pub f32);
+impl RunOutcome {
+    pub fn message(self) -> &'static str {
+        match self {
+            RunOutcome::Died(cause) => cause.message(),
+            RunOutcome::Finished => "You reached the finish line",
+        }
+    }
+}
 
 pub struct Points(pub u32);
 
@@ -76,4 +114,13 @@ impl Points {
     pub fn new() -> Self {
         Self(0)
     }
-}
\ No newline at end of file
+}
+
+/// Texture ids for sprites created by gameplay systems, these are all 0 when
+/// running headless as nothing gets drawn
+#[derive(Clone, Copy, Default)]
+pub struct Textures {
+    pub player: u64,
+    pub aeroplane: u64,
+    pub marker: u64,
+}
@@ -0,0 +1,277 @@
+use std::{
+    fmt,
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::SystemTime,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    consts::*,
+    difficulty::DifficultyCurve,
+};
+
+pub const DEFAULT_CONFIG_PATH: &str = "./assets/game_config.ron";
+
+/// Gameplay tuning values, any field missing from the file keeps its default
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameConfig {
+    /// Pixels the map and planes scroll left each frame
+    pub scroll_rate: f32,
+    /// Height the player loses each frame
+    pub fall_speed: f32,
+    /// Height the player starts at and is reset to when jumping off a plane
+    pub start_height: f32,
+    pub player_speed: f32,
+    /// Points for each tilled tile that gets grown
+    pub points_grow: u32,
+    /// Frames between plane spawns
+    pub spawn_interval: i32,
+    /// Only read when a run starts, edits made mid run apply to the next one
+    pub max_floor_height: u8,
+    pub difficulty: DifficultyCurve,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            scroll_rate: SCROLL_RATE,
+            fall_speed: FALL_SPEED,
+            start_height: START_HEIGHT,
+            player_speed: PLAYER_SPEED,
+            points_grow: POINTS_GROW,
+            spawn_interval: 70,
+            max_floor_height: MAX_FLOOR_HEIGHT,
+            difficulty: DifficultyCurve::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(ron::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "could not read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "could not parse config: {}", e),
+            ConfigError::Invalid(msg) => write!(f, "invalid config: {}", msg),
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, ConfigError> {
+        let config: GameConfig = ron::de::from_str(contents).map_err(ConfigError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads the config at `path`, falling back to the defaults if it is missing or invalid
+    pub fn load_or_default(path: &Path) -> Self {
+        match Self::load(path) {
+            Ok(config) => config,
+            Err(ConfigError::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                eprintln!("{}: {}, using default values", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Describes every value that differs between `self` and `new`
+    pub fn changes(&self, new: &GameConfig) -> Vec<String> {
+        let mut changes = Vec::new();
+        macro_rules! compare {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field != new.$field {
+                        changes.push(format!("{}: {} -> {}", stringify!($field), self.$field, new.$field));
+                    }
+                )*
+            };
+        }
+        compare!(scroll_rate, fall_speed, start_height, player_speed, points_grow, spawn_interval, max_floor_height);
+        if self.difficulty != new.difficulty {
+            changes.push(format!("difficulty: {:?} -> {:?}", self.difficulty, new.difficulty));
+        }
+        changes
+    }
+
+    /// A hash of every value, stable between runs and builds so replays can
+    /// check they are played with the config they were recorded with
+    pub fn fingerprint(&self) -> u64 {
+        // FNV-1a, std's hasher is allowed to change between releases
+        format!("{:?}", self).bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        fn positive(name: &str, value: f32) -> Result<(), ConfigError> {
+            if value.is_finite() && value > 0. {
+                Ok(())
+            } else {
+                Err(ConfigError::Invalid(format!("{} must be greater than 0, got {}", name, value)))
+            }
+        }
+
+        positive("scroll_rate", self.scroll_rate)?;
+        positive("fall_speed", self.fall_speed)?;
+        positive("start_height", self.start_height)?;
+        positive("player_speed", self.player_speed)?;
+
+        if self.spawn_interval <= 0 {
+            return Err(ConfigError::Invalid(format!("spawn_interval must be greater than 0, got {}", self.spawn_interval)));
+        }
+        if self.max_floor_height > MAX_BRICK_HEIGHT {
+            return Err(ConfigError::Invalid(format!(
+                "max_floor_height must be at most {}, got {}",
+                MAX_BRICK_HEIGHT, self.max_floor_height
+            )));
+        }
+        self.difficulty.validate().map_err(ConfigError::Invalid)?;
+
+        Ok(())
+    }
+}
+
+/// How many frames to wait between checking the config file for changes
+const WATCH_INTERVAL: u32 = 30;
+
+/// Watches the config file's modification time so edits can be picked up mid run
+pub struct ConfigWatcher {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+    frames_until_check: u32,
+}
+
+impl ConfigWatcher {
+    /// `last_modified` is the file's modification time when the current config
+    /// was read, edits made since then are picked up by the first check
+    pub fn new(path: PathBuf, last_modified: Option<SystemTime>) -> Self {
+        Self {
+            path,
+            last_modified,
+            frames_until_check: WATCH_INTERVAL,
+        }
+    }
+
+    /// Call once a frame, returns the reloaded config when the file has changed
+    pub fn poll(&mut self) -> Option<Result<GameConfig, ConfigError>> {
+        if self.frames_until_check > 0 {
+            self.frames_until_check -= 1;
+            return None;
+        }
+        self.frames_until_check = WATCH_INTERVAL;
+
+        self.check()
+    }
+
+    /// Checks the file right away, returns the reloaded config when it has changed
+    pub fn check(&mut self) -> Option<Result<GameConfig, ConfigError>> {
+        let modified = modified(&self.path);
+        if modified.is_none() || modified == self.last_modified {
+            return None;
+        }
+        self.last_modified = modified;
+
+        Some(GameConfig::load(&self.path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        self.last_modified
+    }
+}
+
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_shipped_config() {
+        let config = GameConfig::parse(include_str!("../../assets/game_config.ron")).unwrap();
+        assert_eq!(config.scroll_rate, 4.0);
+        assert_eq!(config.spawn_interval, 70);
+        assert_eq!(config.difficulty.ramp_distance, 5000.0);
+    }
+
+    #[test]
+    fn missing_fields_keep_their_defaults() {
+        let config = GameConfig::parse("(points_grow: 5)").unwrap();
+        assert_eq!(config, GameConfig {
+            points_grow: 5,
+            ..GameConfig::default()
+        });
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(matches!(GameConfig::parse("(scroll_speed: 3.0)"), Err(ConfigError::Parse(_))));
+        assert!(matches!(GameConfig::parse("(difficulty: (ramp: 1.0))"), Err(ConfigError::Parse(_))));
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        for contents in ["(fall_speed: -1.0)", "(spawn_interval: 0)", "(max_floor_height: 9)"].iter() {
+            match GameConfig::parse(contents) {
+                Err(ConfigError::Invalid(_)) => {}
+                other => panic!("{} gave {:?}", contents, other),
+            }
+        }
+    }
+
+    #[test]
+    fn changes_lists_exactly_the_changed_fields() {
+        let old = GameConfig::default();
+        assert!(old.changes(&old.clone()).is_empty());
+
+        let mut new = old.clone();
+        new.scroll_rate = 8.;
+        new.spawn_interval = 50;
+        new.difficulty.exponent = 2.;
+        let changes = old.changes(&new);
+
+        assert_eq!(changes.len(), 3, "{:?}", changes);
+        assert_eq!(changes[0], "scroll_rate: 4 -> 8");
+        assert_eq!(changes[1], "spawn_interval: 70 -> 50");
+        assert!(changes[2].starts_with("difficulty: "), "{:?}", changes);
+    }
+
+    #[test]
+    fn watcher_reloads_once_per_edit() {
+        let path = std::env::temp_dir().join(format!("guacamole-config-test-{}", std::process::id()));
+        fs::write(&path, "(points_grow: 3)").unwrap();
+
+        let mut watcher = ConfigWatcher::new(path.clone(), None);
+        let reloaded = watcher.check();
+        let again = watcher.check();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(reloaded.unwrap().unwrap().points_grow, 3);
+        assert!(again.is_none());
+    }
+}
@@ -13,6 +13,7 @@ pub mod textures {
 pub mod draw_layers {
     pub const PLAYER: f32 = 10.0;
     pub const PLANE: f32 = 5.0;
+    pub const POWERUP: f32 = 2.0;
     pub const WALL: f32 = 1.0;
     pub const FLOOR: f32 = 0.0;
 }
@@ -31,8 +32,15 @@ pub const PLAYER_SPEED: f32 = 5.0;
 pub const MAX_FLOOR_HEIGHT: u8 = 2;
 pub const MAX_BRICK_HEIGHT: u8 = 4;
 
-pub const WIDTH: usize = 100000;
+/// Columns generated at a time as the map scrolls
+pub const CHUNK_WIDTH: usize = 60;
 pub const HEIGHT: usize = 10;
+/// Map column a level's first grid column is placed at, far enough right that
+/// the bottom row's first tile still has a column of at least 0
+pub const LEVEL_FIRST_COLUMN: i32 = HEIGHT as i32 / 2;
+
+pub const WINDOW_WIDTH: i32 = 1280;
+pub const WINDOW_HEIGHT: i32 = 720;
 
 pub const CLEAR_COL: crate::tetra::graphics::Color = crate::tetra::graphics::Color::rgb(0.392, 0.584, 0.929);
 
@@ -41,4 +49,31 @@ pub const SCROLL_RATE: f32 = 4.;
 pub const FALL_SPEED: f32 = 0.01;
 pub const START_HEIGHT: f32 = 1.0;
 
-pub const POINTS_GROW: u32 = 1;
\ No newline at end of file
+pub const POINTS_GROW: u32 = 1;
+/// Frames of growing in a row needed for each step of the combo multiplier
+pub const COMBO_STEP_FRAMES: u32 = 10;
+pub const MAX_COMBO_MULTIPLIER: u32 = 5;
+
+/// Fraction of `start_height` the player can fall to before the jump chain breaks
+pub const CHAIN_HEIGHT: f32 = 0.3;
+/// Multiplier gained for each plane in the jump chain
+pub const CHAIN_STEP: f32 = 0.5;
+pub const MAX_CHAIN_MULTIPLIER: f32 = 4.;
+
+/// Points per tile for growing a whole tilled strip
+pub const STRIP_BONUS: u32 = 5;
+
+/// Frames between power-up spawns
+pub const POWERUP_INTERVAL: i32 = 60 * 6;
+/// marker.png, which pickups are drawn with, is this many pixels across
+pub const MARKER_SIZE: f32 = 4.;
+/// Pickups are drawn this many times the marker's size, about a floor tile across
+pub const POWERUP_SCALE: f32 = 8.;
+/// Fall speed multiplier while the parachute is active
+pub const PARACHUTE_FALL_MULTIPLIER: f32 = 0.4;
+/// Tile radius grown around the player with wide planting, normally 1
+pub const WIDE_GROW_RADIUS: i32 = 2;
+/// Planes closer than this are pulled towards the player by the magnet
+pub const MAGNET_RADIUS: f64 = 300.;
+/// Pixels per frame the magnet pulls planes
+pub const MAGNET_PULL: f64 = 2.;
@@ -0,0 +1,181 @@
+use std::fmt;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::{
+    shipyard::{
+        *,
+    },
+    map::{
+        HexMap,
+    },
+    config::{
+        GameConfig,
+    },
+    systems::{
+        SpawnTimer,
+    },
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DifficultyPreset {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Default for DifficultyPreset {
+    fn default() -> Self {
+        DifficultyPreset::Normal
+    }
+}
+
+impl DifficultyPreset {
+    pub const ALL: [DifficultyPreset; 3] = [
+        DifficultyPreset::Easy,
+        DifficultyPreset::Normal,
+        DifficultyPreset::Hard,
+    ];
+
+    /// Multiplier applied to the curve's ramp, easier presets ramp slower
+    fn ramp_scale(self) -> f32 {
+        match self {
+            DifficultyPreset::Easy => 0.6,
+            DifficultyPreset::Normal => 1.0,
+            DifficultyPreset::Hard => 1.4,
+        }
+    }
+
+    /// Multipliers for scroll rate, fall speed and spawn interval at distance 0
+    fn start_multipliers(self) -> (f32, f32, f32) {
+        match self {
+            DifficultyPreset::Easy => (0.85, 0.75, 0.8),
+            DifficultyPreset::Normal => (1.0, 1.0, 1.0),
+            DifficultyPreset::Hard => (1.15, 1.25, 1.2),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            DifficultyPreset::Easy => DifficultyPreset::Normal,
+            DifficultyPreset::Normal => DifficultyPreset::Hard,
+            DifficultyPreset::Hard => DifficultyPreset::Easy,
+        }
+    }
+
+    pub fn prev(self) -> Self {
+        self.next().next()
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|preset| preset.to_string() == name)
+    }
+}
+
+impl fmt::Display for DifficultyPreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// How the tuning values ramp up as the run goes on, part of `GameConfig`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DifficultyCurve {
+    /// Distance in tiles at which the ramp is complete on Normal
+    pub ramp_distance: f32,
+    /// Shapes the ramp, 1.0 is linear and higher values stay easy for longer
+    pub exponent: f32,
+    /// Multiplier on the scroll rate once fully ramped
+    pub max_scroll_multiplier: f32,
+    /// Multiplier on the fall speed once fully ramped
+    pub max_fall_multiplier: f32,
+    /// Multiplier on the spawn interval once fully ramped, fewer planes is harder
+    pub max_spawn_multiplier: f32,
+}
+
+impl Default for DifficultyCurve {
+    fn default() -> Self {
+        Self {
+            ramp_distance: 5000.,
+            exponent: 1.,
+            max_scroll_multiplier: 2.,
+            max_fall_multiplier: 1.5,
+            max_spawn_multiplier: 1.5,
+        }
+    }
+}
+
+impl DifficultyCurve {
+    pub fn validate(&self) -> Result<(), String> {
+        let values = [
+            ("ramp_distance", self.ramp_distance),
+            ("exponent", self.exponent),
+            ("max_scroll_multiplier", self.max_scroll_multiplier),
+            ("max_fall_multiplier", self.max_fall_multiplier),
+            ("max_spawn_multiplier", self.max_spawn_multiplier),
+        ];
+
+        for (name, value) in values.iter() {
+            if !value.is_finite() || *value <= 0. {
+                return Err(format!("difficulty.{} must be greater than 0, got {}", name, value));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The multipliers currently applied to `GameConfig` tuning values
+pub struct Difficulty {
+    pub preset: DifficultyPreset,
+    pub scroll_multiplier: f32,
+    pub fall_multiplier: f32,
+    pub spawn_multiplier: f32,
+}
+
+impl Difficulty {
+    pub fn new(preset: DifficultyPreset) -> Self {
+        let (scroll_multiplier, fall_multiplier, spawn_multiplier) = preset.start_multipliers();
+        Self {
+            preset,
+            scroll_multiplier,
+            fall_multiplier,
+            spawn_multiplier,
+        }
+    }
+
+    pub fn scroll_rate(&self, config: &GameConfig) -> f32 {
+        config.scroll_rate * self.scroll_multiplier
+    }
+
+    pub fn fall_speed(&self, config: &GameConfig) -> f32 {
+        config.fall_speed * self.fall_multiplier
+    }
+
+    pub fn spawn_interval(&self, config: &GameConfig) -> i32 {
+        ((config.spawn_interval as f32 * self.spawn_multiplier) as i32).max(1)
+    }
+}
+
+fn lerp(start: f32, end: f32, t: f32) -> f32 {
+    start + (end - start) * t
+}
+
+/// Ramps the difficulty multipliers along the config's curve based on distance travelled
+pub fn ramp_difficulty(config: UniqueView<GameConfig>, map: UniqueView<HexMap>, mut difficulty: UniqueViewMut<Difficulty>, mut spawn_timer: UniqueViewMut<SpawnTimer>) {
+    let curve = &config.difficulty;
+    let preset = difficulty.preset;
+
+    let progress = map.distance() as f32 * preset.ramp_scale() / curve.ramp_distance;
+    let t = progress.min(1.).powf(curve.exponent);
+
+    let (scroll, fall, spawn) = preset.start_multipliers();
+    difficulty.scroll_multiplier = lerp(scroll, scroll * curve.max_scroll_multiplier, t);
+    difficulty.fall_multiplier = lerp(fall, fall * curve.max_fall_multiplier, t);
+    difficulty.spawn_multiplier = lerp(spawn, spawn * curve.max_spawn_multiplier, t);
+
+    spawn_timer.set_max(difficulty.spawn_interval(&config));
+}
@@ -0,0 +1,446 @@
+use std::{
+    io,
+    path::PathBuf,
+};
+
+use crate::{
+    Res,
+    shipyard::{
+        *,
+    },
+    consts::{
+        *,
+    },
+    hex::{
+        Axial,
+        Offset,
+    },
+    map::{
+        render_hex_map,
+        HexMap,
+    },
+    level::{
+        self,
+        Level,
+        LevelError,
+        PlaneSpawn,
+    },
+    components::{
+        Direction,
+        FlightPattern,
+    },
+    difficulty::{
+        Difficulty,
+    },
+    tetra::{
+        self,
+        Context,
+        graphics::{
+            self,
+            Camera,
+            Canvas,
+            Color,
+            DrawParams,
+            text::{
+                Text,
+                Font,
+            },
+        },
+        input::{
+            self,
+            Key,
+            MouseButton,
+        },
+        math::{
+            Mat4,
+            Vec2,
+            Vec3,
+        },
+    },
+};
+
+use vermarine_lib::{
+    rendering::{
+        RenderingWorkloadCreator,
+        RenderingWorkloadSystems,
+        draw_buffer::{
+            DrawBuffer,
+            DrawCommand,
+        },
+        Drawables,
+    },
+    pushdown_automaton_state::{
+        PDAState,
+        Trans,
+    },
+};
+
+/// Where the editor saves when the game wasn't started with `--level`
+pub const DEFAULT_LEVEL_PATH: &str = "./assets/levels/custom.level";
+
+/// Width of the level the editor starts with when there isn't one loaded
+const NEW_LEVEL_WIDTH: usize = 60;
+
+/// Background pixels the view moves each frame a scroll key is held
+const SCROLL_SPEED: f32 = 6.;
+
+/// Width of the background canvas, planes spawn as their column reaches its right edge
+const VIEW_WIDTH: f32 = 640.;
+
+/// Screen x planes flying up or down are placed at
+const SPAWN_X: i32 = 1040;
+
+/// Number keys that place each kind of plane, in the order `new_spawn` takes them
+const SPAWN_KEYS: [Key; 5] = [
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+];
+
+/// A plane of the `kind`th pattern, planes crossing the screen fly at `y` and
+/// the rest come in from whichever edge `y` is nearer
+fn new_spawn(kind: usize, frame: u64, y: i32) -> PlaneSpawn {
+    let direction = if y < WINDOW_HEIGHT / 2 { Direction::Down } else { Direction::Up };
+    let (x, y, pattern) = match kind {
+        0 => (SPAWN_X, level::edge_y(direction), FlightPattern::Diagonal(direction)),
+        1 => (WINDOW_WIDTH + 72, y, FlightPattern::Cruiser),
+        2 => (WINDOW_WIDTH + 72, y, FlightPattern::SineWave {
+            base_y: y as f64,
+            amplitude: 60.,
+            period: 90.,
+        }),
+        3 => (SPAWN_X, level::edge_y(direction), FlightPattern::Banking {
+            direction,
+            turn_y: (WINDOW_HEIGHT / 2) as f64,
+            turned: false,
+        }),
+        _ => (SPAWN_X, level::edge_y(direction), FlightPattern::Heavy(direction)),
+    };
+
+    PlaneSpawn {
+        frame,
+        x,
+        y,
+        pattern,
+    }
+}
+
+/// Small marker texture drawn over the center of `hex`
+fn marker(texture: u64, map: &HexMap, hex: Axial, color: Color, scale: f32) -> Option<DrawCommand> {
+    let height = map.get(hex)?.wall_height;
+    let (x, y) = map.axial_to_pixel(hex);
+    Some(
+        DrawCommand::new(texture)
+            .position(Vec3::new(x - MARKER_SIZE / 2. * scale, y - MARKER_SIZE / 2. * scale, height as f32 * FLOOR_DEPTH_STEP))
+            .scale(Vec2::new(scale, scale))
+            .color(color)
+            .draw_iso(true)
+    )
+}
+
+/// Edits a `Level` by pointing at tiles on the map, the map is regenerated
+/// from the level after every change
+pub struct EditorState {
+    world: World,
+    canvas: Canvas,
+    text: Text,
+    level: Level,
+    path: PathBuf,
+    /// How far the map scrolls each frame when played, turns spawn frames into columns
+    scroll_rate: f32,
+    /// `HexMap::position.x` of the view, never above 0
+    scroll: f32,
+    hovered: Option<Axial>,
+    /// Result of the last save or load
+    status: String,
+}
+
+impl EditorState {
+    pub fn new(ctx: &mut Context, res: &mut Res) -> tetra::Result<Self> {
+        let world = World::new();
+        world.add_unique_non_send_sync(Drawables::new(ctx).unwrap());
+        world
+            .add_rendering_workload(ctx)
+            .with_rendering_systems()
+            .build();
+        world.run(|mut camera: UniqueViewMut<Camera>| {
+            camera.position = Vec2::new(640., 360.);
+        });
+
+        // Picks up where the last save left off, which isn't in `res.level` as
+        // saving doesn't change the level normal runs use
+        let path = res.level_path.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_LEVEL_PATH));
+        let (level, status) = match Level::load(&path) {
+            Ok(level) => (level, format!("Loaded {}", path.display())),
+            Err(LevelError::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => (Level::new(NEW_LEVEL_WIDTH), String::new()),
+            Err(e) => (Level::new(NEW_LEVEL_WIDTH), format!("{}: {}", path.display(), e)),
+        };
+        world.add_unique(HexMap::with_generator(HEIGHT, 0, res.config.max_floor_height, Box::new(level.generator())));
+
+        Ok(Self {
+            world,
+            canvas: Canvas::new(ctx, 640, 360)?,
+            text: Text::new("", Font::vector(ctx, "./assets/DejaVuSansMono.ttf", 16.0)?),
+            level,
+            path,
+            scroll_rate: Difficulty::new(res.difficulty).scroll_rate(&res.config),
+            scroll: 0.,
+            hovered: None,
+            status,
+        })
+    }
+
+    /// Leftmost column planes can be placed in, the ones before it are already
+    /// on screen when the run starts so `column_frame` can't tell them apart
+    fn first_spawn_column() -> i32 {
+        (VIEW_WIDTH / FLOOR_WIDTH).ceil() as i32
+    }
+
+    /// Frame a column reaches the right edge of the screen when played, this
+    /// ignores the difficulty ramping up so later spawns are approximate.
+    /// Only meaningful from `first_spawn_column` on
+    fn column_frame(&self, column: i32) -> u64 {
+        ((column as f32 * FLOOR_WIDTH - VIEW_WIDTH) / self.scroll_rate).max(0.) as u64
+    }
+
+    fn frame_column(&self, frame: u64) -> i32 {
+        ((frame as f32 * self.scroll_rate + VIEW_WIDTH) / FLOOR_WIDTH).round() as i32
+    }
+
+    /// Regenerates the map from the level, only needed when the level changes
+    fn rebuild_map(&self) {
+        self.world.run(|mut map: UniqueViewMut<HexMap>| {
+            let mut rebuilt = HexMap::with_generator(HEIGHT, map.seed, map.max_floor_height, Box::new(self.level.generator()));
+            rebuilt.position.x = self.scroll;
+            rebuilt.stream_chunks();
+            *map = rebuilt;
+        });
+    }
+
+    /// Applies whatever edit keys are pressed to `hex`, returns true if the level changed
+    fn edit(&mut self, ctx: &Context, hex: Axial, mouse_y: i32) -> bool {
+        let pressed = |key| input::is_key_pressed(ctx.input_context(), key);
+        let clicked = |button| input::is_mouse_button_pressed(ctx.input_context(), button);
+        let mut changed = false;
+        // The level's columns, hex.q slants across the screen as rows go down
+        let column = Offset::from(hex).col;
+
+        for (kind, key) in SPAWN_KEYS.iter().enumerate() {
+            if !pressed(*key) {
+                continue;
+            }
+            if column < Self::first_spawn_column() {
+                self.status = format!("Planes can only be placed from column {} on", Self::first_spawn_column());
+                continue;
+            }
+
+            let frame = self.column_frame(column);
+            self.level.spawns.push(new_spawn(kind, frame, mouse_y));
+            self.level.spawns.sort_by_key(|spawn| spawn.frame);
+            changed = true;
+        }
+        if pressed(Key::Delete) || pressed(Key::Backspace) {
+            let spawns = std::mem::take(&mut self.level.spawns);
+            self.level.spawns = spawns
+                .into_iter()
+                .filter(|spawn| self.frame_column(spawn.frame) != column)
+                .collect();
+            changed = true;
+        }
+        if pressed(Key::F) {
+            self.level.finish = column.max(0) as u32;
+            changed = true;
+        }
+
+        // Only look the tile up when editing it as that widens the level to fit it
+        let editing = clicked(MouseButton::Left) || clicked(MouseButton::Right)
+            || [Key::E, Key::Q, Key::T, Key::G].iter().any(|key| pressed(*key));
+        if !editing {
+            return changed;
+        }
+        let tile = match self.level.tile_mut(hex) {
+            Some(tile) => tile,
+            None => return changed,
+        };
+        let wall = tile.wall.max(tile.ground);
+
+        if clicked(MouseButton::Left) && tile.ground < MAX_BRICK_HEIGHT {
+            tile.ground += 1;
+        }
+        if clicked(MouseButton::Right) && tile.ground > 0 {
+            tile.ground -= 1;
+        }
+        if pressed(Key::E) && wall < MAX_BRICK_HEIGHT {
+            tile.wall = wall + 1;
+        }
+        if pressed(Key::Q) && tile.wall > tile.ground {
+            tile.wall -= 1;
+        }
+        if pressed(Key::T) {
+            tile.tilled = !tile.tilled;
+            tile.grown &= tile.tilled;
+        }
+        if pressed(Key::G) {
+            tile.grown = !tile.grown;
+            tile.tilled |= tile.grown;
+        }
+
+        // Tilled tiles never have walls so they are always safe to land on
+        if tile.tilled || tile.wall <= tile.ground {
+            tile.wall = 0;
+        }
+
+        true
+    }
+
+    /// Writes the level out for "Play Level" in the menu to pick up
+    fn save(&mut self, res: &mut Res) {
+        self.status = match self.level.save(&self.path) {
+            Ok(()) => {
+                res.level_path = Some(self.path.clone());
+                format!("Saved {}", self.path.display())
+            }
+            Err(e) => format!("Could not save {}: {}", self.path.display(), e),
+        };
+    }
+
+    fn load(&mut self) {
+        self.status = match Level::load(&self.path) {
+            Ok(level) => {
+                self.level = level;
+                format!("Loaded {}", self.path.display())
+            }
+            Err(e) => format!("{}: {}", self.path.display(), e),
+        };
+    }
+
+    fn describe_hovered(&self) -> String {
+        let hex = match self.hovered {
+            Some(hex) => hex,
+            None => return String::from("-"),
+        };
+
+        // Where the tile is in the level file's grids
+        let (col, row) = level::grid_position(hex);
+        self.world.run(|map: UniqueView<HexMap>| {
+            match map.get(hex) {
+                Some(tile) => format!(
+                    "{}, {} ground {} wall {}{}{}",
+                    col, row, tile.ground_height, tile.wall_height,
+                    if tile.is_tilled { " tilled" } else { "" },
+                    if tile.is_grown { " grown" } else { "" },
+                ),
+                None => String::from("-"),
+            }
+        })
+    }
+}
+
+impl PDAState<Res> for EditorState {
+    fn update(&mut self, ctx: &mut Context, res: &mut Res) -> tetra::Result<Trans<Res>> {
+        if input::is_key_pressed(ctx.input_context(), Key::Escape) {
+            return Ok(Trans::Pop);
+        }
+
+        let mut changed = false;
+
+        let mut scroll = 0.;
+        if input::is_key_down(ctx.input_context(), Key::Left) || input::is_key_down(ctx.input_context(), Key::A) {
+            scroll += SCROLL_SPEED;
+        }
+        if input::is_key_down(ctx.input_context(), Key::Right) || input::is_key_down(ctx.input_context(), Key::D) {
+            scroll -= SCROLL_SPEED;
+        }
+        if scroll != 0. {
+            self.scroll = (self.scroll + scroll).min(0.);
+            let position = self.scroll;
+            self.world.run(|mut map: UniqueViewMut<HexMap>| {
+                map.position.x = position;
+                map.stream_chunks();
+            });
+        }
+
+        // The map is drawn to a canvas half the size of the window
+        let mouse = input::get_mouse_position(ctx.input_context());
+        self.hovered = self.world.run(|map: UniqueView<HexMap>| map.pixel_to_hex(mouse / 2.));
+        if let Some(hex) = self.hovered {
+            changed |= self.edit(ctx, hex, mouse.y as i32);
+        }
+
+        if input::is_key_pressed(ctx.input_context(), Key::S) {
+            self.save(res);
+        }
+        if input::is_key_pressed(ctx.input_context(), Key::L) {
+            self.load();
+            changed = true;
+        }
+
+        if changed {
+            self.rebuild_map();
+        }
+
+        Ok(Trans::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _res: &mut Res) -> tetra::Result {
+        self.world.run(|mut draw_buff: UniqueViewMut<DrawBuffer>| {
+            draw_buff.transform_mat = Mat4::identity();
+        });
+
+        graphics::set_canvas(ctx, &self.canvas);
+        graphics::clear(ctx, CLEAR_COL);
+        self.world.run(render_hex_map);
+
+        let spawn_columns: Vec<i32> = self.level.spawns.iter().map(|spawn| self.frame_column(spawn.frame)).collect();
+        let (hovered, finish) = (self.hovered, self.level.finish as i32);
+        self.world.run(|mut draw_buffer: UniqueViewMut<DrawBuffer>, drawables: NonSendSync<UniqueView<Drawables>>, map: UniqueView<HexMap>| {
+            let texture = drawables.alias[textures::MARKER];
+            let mut markers = Vec::new();
+
+            for r in 0..map.height as i32 {
+                markers.extend(marker(texture, &map, Offset { col: finish, row: r }.into(), Color::WHITE, 2.));
+            }
+            for &column in &spawn_columns {
+                markers.extend(marker(texture, &map, Offset { col: column, row: 0 }.into(), Color::rgb(1.0, 0.2, 0.2), 3.));
+            }
+            if let Some(hex) = hovered {
+                markers.extend(marker(texture, &map, hex, Color::rgb(1.0, 0.9, 0.2), 3.));
+            }
+
+            draw_buffer.new_command_pool(true);
+            draw_buffer.get_command_pool().commands.extend(&markers);
+            draw_buffer.end_command_pool();
+        });
+
+        self.world.run_with_data(DrawBuffer::flush, ctx);
+        graphics::flush(ctx);
+        graphics::reset_canvas(ctx);
+
+        graphics::clear(ctx, CLEAR_COL);
+        graphics::draw(ctx, &self.canvas,
+            DrawParams::new()
+            .scale(Vec2::new(2., 2.))
+        );
+
+        self.text.set_content(format!(
+"{} - {} tiles wide, {} planes, finish at {}
+Hovered: {}
+{}
+
+<CLICK>/<RIGHT CLICK> raise/lower ground
+<E>/<Q> raise/lower wall
+<T> tilled  <G> grown  <F> finish line here
+<1>-<5> diagonal, cruiser, sine, banking or heavy plane
+<DELETE> remove planes in this column
+<A>/<D> scroll  <S> save  <L> load  <ESCAPE> back",
+            self.level.name, self.level.width, self.level.spawns.len(), self.level.finish,
+            self.describe_hovered(),
+            self.status,
+        ));
+        graphics::draw(ctx, &self.text, Vec2::new(40., 20.));
+
+        Ok(())
+    }
+}
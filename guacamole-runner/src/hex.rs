@@ -0,0 +1,304 @@
+use std::ops::{
+    Add,
+    Sub,
+    Mul,
+    Neg,
+};
+
+/// Axial hex coords, `q` is the map's column and `r` its row. This is how
+/// `HexMap` stores its tiles, rows further down are shifted half a tile right
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Axial {
+    pub q: i32,
+    pub r: i32,
+}
+
+/// Cube hex coords with `q + r + s == 0`, the geometry helpers work in these
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Cube {
+    pub q: i32,
+    pub r: i32,
+    pub s: i32,
+}
+
+/// "odd-r" offset coords where every odd row is shifted half a tile right,
+/// these line up with a rectangular grid
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Offset {
+    pub col: i32,
+    pub row: i32,
+}
+
+/// A point somewhere inside a hex, round it to get the hex it is in. Stored as
+/// f64 so points stay precise far from the origin as the map scrolls on
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FractionalHex {
+    pub q: f64,
+    pub r: f64,
+    pub s: f64,
+}
+
+impl Axial {
+    pub fn new(q: i32, r: i32) -> Self {
+        Self { q, r }
+    }
+
+    #[allow(dead_code)]
+    pub fn distance(self, other: Axial) -> i32 {
+        Cube::from(self).distance(other.into())
+    }
+}
+
+impl Cube {
+    /// Starts east and goes anticlockwise, `ring` relies on this order
+    pub const DIRECTIONS: [Cube; 6] = [
+        Cube { q: 1, r: 0, s: -1 },
+        Cube { q: 1, r: -1, s: 0 },
+        Cube { q: 0, r: -1, s: 1 },
+        Cube { q: -1, r: 0, s: 1 },
+        Cube { q: -1, r: 1, s: 0 },
+        Cube { q: 0, r: 1, s: -1 },
+    ];
+
+    /// Panics in debug builds if the coords are not on the `q + r + s == 0` plane
+    pub fn new(q: i32, r: i32, s: i32) -> Self {
+        debug_assert_eq!(q + r + s, 0, "cube coords must sum to 0");
+        Self { q, r, s }
+    }
+
+    pub fn neighbour(self, direction: usize) -> Cube {
+        self + Self::DIRECTIONS[direction % 6]
+    }
+
+    #[allow(dead_code)]
+    pub fn neighbours(self) -> [Cube; 6] {
+        let mut neighbours = Self::DIRECTIONS;
+        for neighbour in neighbours.iter_mut() {
+            *neighbour = self + *neighbour;
+        }
+        neighbours
+    }
+
+    /// Number of steps between two hexes
+    pub fn distance(self, other: Cube) -> i32 {
+        let delta = self - other;
+        (delta.q.abs() + delta.r.abs() + delta.s.abs()) / 2
+    }
+
+    /// Every hex exactly `radius` steps away, a ring of radius 0 is just `self`
+    pub fn ring(self, radius: i32) -> Vec<Cube> {
+        if radius <= 0 {
+            return vec![self];
+        }
+
+        let mut ring = Vec::with_capacity(6 * radius as usize);
+        let mut cube = self + Self::DIRECTIONS[4] * radius;
+        for direction in 0..6 {
+            for _ in 0..radius {
+                ring.push(cube);
+                cube = cube.neighbour(direction);
+            }
+        }
+        ring
+    }
+
+    /// Every hex within `radius` ordered from the center outwards ring by ring
+    #[allow(dead_code)]
+    pub fn spiral(self, radius: i32) -> Vec<Cube> {
+        (0..=radius.max(0)).flat_map(|ring| self.ring(ring)).collect()
+    }
+
+    /// Every hex within `radius`, cheaper than `spiral` when order doesn't matter
+    pub fn range(self, radius: i32) -> Vec<Cube> {
+        let mut range = Vec::new();
+        for q in -radius..=radius {
+            for r in (-radius).max(-q - radius)..=radius.min(-q + radius) {
+                range.push(self + Cube::new(q, r, -q - r));
+            }
+        }
+        range
+    }
+
+    /// Hexes on the straight line to `other`, both ends included
+    #[allow(dead_code)]
+    pub fn line(self, other: Cube) -> Vec<Cube> {
+        let steps = self.distance(other);
+        if steps == 0 {
+            return vec![self];
+        }
+
+        // Nudged so points exactly on an edge between two hexes always round the same way
+        let start = FractionalHex::from(self) + FractionalHex { q: 1e-6, r: 1e-6, s: -2e-6 };
+        let end = FractionalHex::from(other) + FractionalHex { q: 1e-6, r: 1e-6, s: -2e-6 };
+        (0..=steps)
+            .map(|step| start.lerp(end, step as f64 / steps as f64).round())
+            .collect()
+    }
+}
+
+impl FractionalHex {
+    pub fn new(q: f64, r: f64) -> Self {
+        Self { q, r, s: -q - r }
+    }
+
+    pub fn lerp(self, other: FractionalHex, t: f64) -> Self {
+        Self {
+            q: self.q + (other.q - self.q) * t,
+            r: self.r + (other.r - self.r) * t,
+            s: self.s + (other.s - self.s) * t,
+        }
+    }
+
+    /// The hex this point is in
+    pub fn round(self) -> Cube {
+        let mut qi = self.q.round() as i32;
+        let mut ri = self.r.round() as i32;
+        let mut si = self.s.round() as i32;
+
+        let q_diff = (qi as f64 - self.q).abs();
+        let r_diff = (ri as f64 - self.r).abs();
+        let s_diff = (si as f64 - self.s).abs();
+
+        if q_diff > r_diff && q_diff > s_diff {
+            qi = -ri - si;
+        } else if r_diff > s_diff {
+            ri = -qi - si;
+        } else {
+            si = -qi - ri;
+        }
+
+        Cube::new(qi, ri, si)
+    }
+}
+
+impl From<Axial> for Cube {
+    fn from(hex: Axial) -> Self {
+        Cube::new(hex.q, hex.r, -hex.q - hex.r)
+    }
+}
+
+impl From<Cube> for Axial {
+    fn from(hex: Cube) -> Self {
+        Axial::new(hex.q, hex.r)
+    }
+}
+
+impl From<Offset> for Axial {
+    fn from(hex: Offset) -> Self {
+        Axial::new(hex.col - (hex.row - (hex.row & 1)) / 2, hex.row)
+    }
+}
+
+impl From<Axial> for Offset {
+    fn from(hex: Axial) -> Self {
+        Offset {
+            col: hex.q + (hex.r - (hex.r & 1)) / 2,
+            row: hex.r,
+        }
+    }
+}
+
+impl From<Offset> for Cube {
+    fn from(hex: Offset) -> Self {
+        Axial::from(hex).into()
+    }
+}
+
+impl From<Cube> for Offset {
+    fn from(hex: Cube) -> Self {
+        Axial::from(hex).into()
+    }
+}
+
+impl From<Cube> for FractionalHex {
+    fn from(hex: Cube) -> Self {
+        FractionalHex {
+            q: hex.q as f64,
+            r: hex.r as f64,
+            s: hex.s as f64,
+        }
+    }
+}
+
+impl From<Axial> for FractionalHex {
+    fn from(hex: Axial) -> Self {
+        Cube::from(hex).into()
+    }
+}
+
+macro_rules! impl_hex_ops {
+    ($hex:ident, $scalar:ty, $($field:ident),+) => {
+        impl Add for $hex {
+            type Output = $hex;
+            fn add(self, other: $hex) -> $hex {
+                $hex { $($field: self.$field + other.$field),+ }
+            }
+        }
+
+        impl Sub for $hex {
+            type Output = $hex;
+            fn sub(self, other: $hex) -> $hex {
+                $hex { $($field: self.$field - other.$field),+ }
+            }
+        }
+
+        impl Mul<$scalar> for $hex {
+            type Output = $hex;
+            fn mul(self, factor: $scalar) -> $hex {
+                $hex { $($field: self.$field * factor),+ }
+            }
+        }
+
+        impl Neg for $hex {
+            type Output = $hex;
+            fn neg(self) -> $hex {
+                $hex { $($field: -self.$field),+ }
+            }
+        }
+    };
+}
+
+impl_hex_ops!(Axial, i32, q, r);
+impl_hex_ops!(Cube, i32, q, r, s);
+impl_hex_ops!(FractionalHex, f64, q, r, s);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_steps_one_hex_at_a_time() {
+        let start = Cube::new(-3, 1, 2);
+        let end = Cube::new(4, -6, 2);
+        let line = start.line(end);
+
+        assert_eq!(line.len() as i32, start.distance(end) + 1);
+        assert_eq!(line.first(), Some(&start));
+        assert_eq!(line.last(), Some(&end));
+        for pair in line.windows(2) {
+            assert_eq!(pair[0].distance(pair[1]), 1);
+        }
+    }
+
+    #[test]
+    fn line_rounds_edges_the_same_way_far_from_the_origin() {
+        // Every other point of this line lands exactly on an edge between two hexes,
+        // the nudge has to survive however large the coords get
+        let offset = Cube::new(1_000_000, -3_000_000, 2_000_000);
+        let near = Cube::new(0, 0, 0).line(Cube::new(2, -4, 2));
+        let far = offset.line(offset + Cube::new(2, -4, 2));
+
+        let shifted: Vec<Cube> = near.iter().map(|&hex| hex + offset).collect();
+        assert_eq!(far, shifted);
+    }
+
+    #[test]
+    fn offset_coords_round_trip() {
+        for row in -3..3 {
+            for col in -3..3 {
+                let hex = Offset { col, row };
+                assert_eq!(Offset::from(Axial::from(hex)), hex);
+            }
+        }
+    }
+}
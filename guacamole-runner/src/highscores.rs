@@ -0,0 +1,255 @@
+use std::{
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use crate::{
+    difficulty::{
+        DifficultyPreset,
+    },
+};
+
+/// How many runs the table keeps for each difficulty
+pub const MAX_SCORES: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct Score {
+    pub points: u32,
+    pub distance: u32,
+    pub seed: u64,
+    /// Date the run finished, formatted as `YYYY-MM-DD`
+    pub date: String,
+    pub difficulty: DifficultyPreset,
+}
+
+impl Score {
+    pub fn new(points: u32, distance: u32, seed: u64, difficulty: DifficultyPreset) -> Self {
+        Self {
+            points,
+            distance,
+            seed,
+            date: chrono::Local::now().format("%Y-%m-%d").to_string(),
+            difficulty,
+        }
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let mut score = Self {
+            points: parts.next()?.parse().ok()?,
+            distance: parts.next()?.parse().ok()?,
+            seed: parts.next()?.parse().ok()?,
+            date: parts.next()?.to_string(),
+            difficulty: DifficultyPreset::default(),
+        };
+
+        // Lines saved before the difficulty was stored go on the default table
+        if let Some(name) = parts.next() {
+            score.difficulty = DifficultyPreset::parse(name)?;
+        }
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(score)
+    }
+}
+
+/// The best runs on each difficulty sorted by points then distance, stored one run per line
+#[derive(Default)]
+pub struct HighScores {
+    scores: Vec<Score>,
+}
+
+impl HighScores {
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("guacamole-runner").join("highscores.txt"))
+    }
+
+    pub fn load() -> Self {
+        Self::path().map(|path| Self::load_from(&path)).unwrap_or_default()
+    }
+
+    /// Loads the table, a missing file gives an empty table and corrupt lines are skipped
+    pub fn load_from(path: &Path) -> Self {
+        let mut scores = Vec::new();
+
+        match fs::read_to_string(path) {
+            Ok(contents) => {
+                for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                    match Score::parse(line) {
+                        Some(score) => scores.push(score),
+                        None => eprintln!("Skipping corrupt high score line in {}: {:?}", path.display(), line),
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!("Could not read high scores from {}: {}", path.display(), e),
+        }
+
+        let mut high_scores = Self {
+            scores,
+        };
+        high_scores.sort();
+        high_scores
+    }
+
+    pub fn save(&self) {
+        if let Some(path) = Self::path() {
+            self.save_to(&path);
+        }
+    }
+
+    pub fn save_to(&self, path: &Path) {
+        let mut out = String::new();
+        for score in &self.scores {
+            out.push_str(&format!("{} {} {} {} {}\n", score.points, score.distance, score.seed, score.date, score.difficulty));
+        }
+
+        let result = match path.parent() {
+            Some(dir) => fs::create_dir_all(dir).and_then(|_| fs::write(path, out)),
+            None => fs::write(path, out),
+        };
+        if let Err(e) = result {
+            eprintln!("Could not save high scores to {}: {}", path.display(), e);
+        }
+    }
+
+    /// The table for one difficulty, best run first
+    pub fn scores(&self, difficulty: DifficultyPreset) -> impl Iterator<Item = &Score> {
+        self.scores.iter().filter(move |score| score.difficulty == difficulty)
+    }
+
+    /// Adds a run to its difficulty's table, returns its rank there if it made the table
+    pub fn insert(&mut self, score: Score) -> Option<usize> {
+        let rank = self.scores(score.difficulty)
+            .position(|other| (score.points, score.distance) > (other.points, other.distance))
+            .unwrap_or_else(|| self.scores(score.difficulty).count());
+
+        if rank >= MAX_SCORES {
+            return None;
+        }
+
+        self.scores.push(score);
+        self.sort();
+        Some(rank)
+    }
+
+    /// Formats a difficulty's table as text, the run at `highlight` is marked with an arrow
+    pub fn table(&self, difficulty: DifficultyPreset, highlight: Option<usize>) -> String {
+        let mut out = String::from("   #  Points  Distance  Date        Seed\n");
+        for (i, score) in self.scores(difficulty).enumerate() {
+            let marker = if highlight == Some(i) { ">" } else { " " };
+            out.push_str(&format!(
+                " {} {:>2}  {:>6}  {:>8}  {}  {}\n",
+                marker, i + 1, score.points, score.distance, score.date, score.seed
+            ));
+        }
+        out
+    }
+
+    /// Sorts every table and drops the runs that fell off the bottom of theirs
+    fn sort(&mut self) {
+        // Stable so a new run ties below the runs already on the table
+        self.scores.sort_by(|a, b| (b.points, b.distance).cmp(&(a.points, a.distance)));
+        for &difficulty in DifficultyPreset::ALL.iter() {
+            let mut kept = 0;
+            self.scores.retain(|score| {
+                if score.difficulty != difficulty {
+                    return true;
+                }
+                kept += 1;
+                kept <= MAX_SCORES
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("guacamole-highscores-{}-{}", name, std::process::id()))
+    }
+
+    fn score(points: u32, difficulty: DifficultyPreset) -> Score {
+        Score {
+            points,
+            distance: points / 10,
+            seed: points as u64,
+            date: String::from("2020-01-01"),
+            difficulty,
+        }
+    }
+
+    fn points(high_scores: &HighScores, difficulty: DifficultyPreset) -> Vec<u32> {
+        high_scores.scores(difficulty).map(|score| score.points).collect()
+    }
+
+    #[test]
+    fn missing_file_gives_an_empty_table() {
+        let high_scores = HighScores::load_from(&temp_path("missing"));
+        assert_eq!(high_scores.scores(DifficultyPreset::Normal).count(), 0);
+    }
+
+    #[test]
+    fn skips_corrupt_lines() {
+        let path = temp_path("corrupt");
+        fs::write(&path, "50 5 1 2020-01-01 Hard\nnot a score\n\n30 3 2 2020-01-02\n10 1 3\n20 2 4 2020-01-03 Impossible\n").unwrap();
+        let high_scores = HighScores::load_from(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(points(&high_scores, DifficultyPreset::Hard), vec![50]);
+        // No difficulty on the line puts it on the default table
+        assert_eq!(points(&high_scores, DifficultyPreset::Normal), vec![30]);
+        assert_eq!(points(&high_scores, DifficultyPreset::Easy), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn saved_tables_load_back() {
+        let path = temp_path("round-trip");
+        let mut high_scores = HighScores::default();
+        high_scores.insert(score(40, DifficultyPreset::Easy));
+        high_scores.insert(score(70, DifficultyPreset::Hard));
+        high_scores.save_to(&path);
+        let loaded = HighScores::load_from(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(points(&loaded, DifficultyPreset::Easy), vec![40]);
+        assert_eq!(points(&loaded, DifficultyPreset::Hard), vec![70]);
+    }
+
+    #[test]
+    fn insert_returns_the_rank_and_keeps_the_top_ten() {
+        let mut high_scores = HighScores::default();
+        for points in (1..=MAX_SCORES as u32).map(|i| i * 10) {
+            high_scores.insert(score(points, DifficultyPreset::Normal));
+        }
+
+        assert_eq!(high_scores.insert(score(55, DifficultyPreset::Normal)), Some(5));
+        assert_eq!(high_scores.insert(score(1000, DifficultyPreset::Normal)), Some(0));
+        // Ties rank below the runs already there
+        assert_eq!(high_scores.insert(score(1000, DifficultyPreset::Normal)), Some(1));
+        assert_eq!(high_scores.insert(score(5, DifficultyPreset::Normal)), None);
+
+        let normal = points(&high_scores, DifficultyPreset::Normal);
+        assert_eq!(normal, vec![1000, 1000, 100, 90, 80, 70, 60, 55, 50, 40]);
+    }
+
+    #[test]
+    fn difficulties_have_their_own_tables() {
+        let mut high_scores = HighScores::default();
+        for _ in 0..MAX_SCORES {
+            high_scores.insert(score(100, DifficultyPreset::Hard));
+        }
+
+        // A full Hard table doesn't push an Easy run off
+        assert_eq!(high_scores.insert(score(1, DifficultyPreset::Easy)), Some(0));
+        assert_eq!(points(&high_scores, DifficultyPreset::Hard).len(), MAX_SCORES);
+        assert_eq!(points(&high_scores, DifficultyPreset::Easy), vec![1]);
+    }
+}
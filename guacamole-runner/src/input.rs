@@ -0,0 +1,41 @@
+use crate::tetra::{
+    InputContext,
+    input::{
+        self,
+        Key,
+    },
+};
+
+/// The keys gameplay systems care about for a single frame
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InputFrame {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl InputFrame {
+    pub fn from_input(ctx: &InputContext) -> Self {
+        Self {
+            up: input::is_key_down(ctx, Key::Up),
+            down: input::is_key_down(ctx, Key::Down),
+            left: input::is_key_down(ctx, Key::Left),
+            right: input::is_key_down(ctx, Key::Right),
+        }
+    }
+
+    /// Packs the frame into the low four bits, used by replay files
+    pub fn to_bits(self) -> u8 {
+        (self.up as u8) | ((self.down as u8) << 1) | ((self.left as u8) << 2) | ((self.right as u8) << 3)
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            up: bits & 0b0001 != 0,
+            down: bits & 0b0010 != 0,
+            left: bits & 0b0100 != 0,
+            right: bits & 0b1000 != 0,
+        }
+    }
+}
@@ -0,0 +1,743 @@
+use std::{
+    fmt,
+    fs,
+    io,
+    path::Path,
+};
+
+use crate::{
+    shipyard::{
+        *,
+    },
+    consts::{
+        *,
+    },
+    hex::{
+        Axial,
+        Offset,
+    },
+    map::{
+        HexChunk,
+        HexMap,
+    },
+    terrain::{
+        ChunkTiles,
+        MapGenerator,
+    },
+    components::{
+        Direction,
+        FlightPattern,
+        Plane,
+    },
+    systems::{
+        self,
+        Landing,
+    },
+};
+
+/// A hand-authored course, loaded from a text file like this:
+///
+/// ```text
+/// # Lines starting with a hash are comments
+/// name Tutorial
+/// # Distance in tiles the player has to reach to finish
+/// finish 120
+/// # spawn <frame> <pattern> <args>, the patterns are:
+/// #   diagonal up|down <x>
+/// #   cruiser <y>
+/// #   sine <y> <amplitude> <period>
+/// #   banking up|down <x> <turn_y>
+/// #   heavy up|down <x>
+/// spawn 0 diagonal down 900
+/// spawn 60 cruiser 300
+/// # One row per map row, each digit is a tile's ground height
+/// ground
+/// 0001112221...
+/// # Optional, digits are wall heights and '.' is no wall
+/// walls
+/// ....33.....
+/// # Optional, '#' is tilled, '*' is tilled and already grown and '.' is neither
+/// tilled
+/// ..##**.....
+/// ```
+///
+/// Every grid needs `HEIGHT` rows of the same width with nothing between them.
+/// Grid columns line up on screen the way they do in the file, each odd row
+/// being half a tile further right. The grid starts `LEVEL_FIRST_COLUMN` tiles
+/// in from the left edge of the map and past its last column the map is flat grass.
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub name: String,
+    pub width: usize,
+    tiles: Vec<LevelTile>,
+    pub spawns: Vec<PlaneSpawn>,
+    pub finish: u32,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LevelTile {
+    pub ground: u8,
+    /// No wall when this isn't above `ground`
+    pub wall: u8,
+    pub tilled: bool,
+    pub grown: bool,
+}
+
+/// A plane the level spawns `frame` frames into the run
+#[derive(Clone, Copy, Debug)]
+pub struct PlaneSpawn {
+    pub frame: u64,
+    pub x: i32,
+    pub y: i32,
+    pub pattern: FlightPattern,
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    /// Line and column are 1-based and point at the problem
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelError::Io(e) => write!(f, "could not read level: {}", e),
+            LevelError::Syntax { line, column, message } => write!(f, "{}:{}: {}", line, column, message),
+        }
+    }
+}
+
+fn syntax_error<T>(line: usize, column: usize, message: impl Into<String>) -> Result<T, LevelError> {
+    Err(LevelError::Syntax {
+        line,
+        column,
+        message: message.into(),
+    })
+}
+
+/// Splits a line into words along with the 1-based column each starts at,
+/// comment lines have no words
+fn words(line: &str) -> Vec<(usize, &str)> {
+    if line.trim_start().starts_with('#') {
+        return Vec::new();
+    }
+
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some(i),
+            (true, Some(s)) => {
+                words.push((s + 1, &line[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        words.push((s + 1, &line[s..]));
+    }
+    words
+}
+
+/// Pulls typed arguments off a line's words, errors point at the bad word or
+/// the end of the line if one is missing
+struct Args<'a> {
+    line: usize,
+    end: usize,
+    words: std::vec::IntoIter<(usize, &'a str)>,
+}
+
+impl<'a> Args<'a> {
+    fn next_word(&mut self, what: &str) -> Result<(usize, &'a str), LevelError> {
+        match self.words.next() {
+            Some(word) => Ok(word),
+            None => syntax_error(self.line, self.end, format!("expected {}", what)),
+        }
+    }
+
+    fn number<T: std::str::FromStr>(&mut self, what: &str) -> Result<T, LevelError> {
+        let (column, word) = self.next_word(what)?;
+        match word.parse() {
+            Ok(value) => Ok(value),
+            Err(_) => syntax_error(self.line, column, format!("expected {}, got '{}'", what, word)),
+        }
+    }
+
+    fn direction(&mut self) -> Result<Direction, LevelError> {
+        let (column, word) = self.next_word("up or down")?;
+        match word {
+            "up" => Ok(Direction::Up),
+            "down" => Ok(Direction::Down),
+            _ => syntax_error(self.line, column, format!("expected up or down, got '{}'", word)),
+        }
+    }
+
+    fn finish(mut self) -> Result<(), LevelError> {
+        match self.words.next() {
+            Some((column, word)) => syntax_error(self.line, column, format!("unexpected '{}'", word)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Start position of a plane flying up or down from the edge of the screen
+pub fn edge_y(direction: Direction) -> i32 {
+    match direction {
+        Direction::Up => WINDOW_HEIGHT + 36,
+        Direction::Down => -36,
+    }
+}
+
+fn parse_spawn(args: &mut Args) -> Result<PlaneSpawn, LevelError> {
+    let frame = args.number("a frame number")?;
+    let (column, pattern) = args.next_word("a flight pattern")?;
+
+    let (x, y, pattern) = match pattern {
+        "diagonal" => {
+            let direction = args.direction()?;
+            (args.number("an x position")?, edge_y(direction), FlightPattern::Diagonal(direction))
+        }
+        "cruiser" => (WINDOW_WIDTH + 72, args.number("a y position")?, FlightPattern::Cruiser),
+        "sine" => {
+            let y: i32 = args.number("a y position")?;
+            let pattern = FlightPattern::SineWave {
+                base_y: y as f64,
+                amplitude: args.number("an amplitude")?,
+                period: args.number("a period in frames")?,
+            };
+            (WINDOW_WIDTH + 72, y, pattern)
+        }
+        "banking" => {
+            let direction = args.direction()?;
+            let x = args.number("an x position")?;
+            let pattern = FlightPattern::Banking {
+                direction,
+                turn_y: args.number("a turn y position")?,
+                turned: false,
+            };
+            (x, edge_y(direction), pattern)
+        }
+        "heavy" => {
+            let direction = args.direction()?;
+            (args.number("an x position")?, edge_y(direction), FlightPattern::Heavy(direction))
+        }
+        _ => return syntax_error(args.line, column, format!("unknown flight pattern '{}'", pattern)),
+    };
+
+    Ok(PlaneSpawn {
+        frame,
+        x,
+        y,
+        pattern,
+    })
+}
+
+impl PlaneSpawn {
+    /// The pattern and its arguments as they are written in a `spawn` line
+    pub fn describe(&self) -> String {
+        let direction_name = |direction: Direction| match direction {
+            Direction::Up => "up",
+            Direction::Down => "down",
+        };
+
+        match self.pattern {
+            FlightPattern::Diagonal(direction) => format!("diagonal {} {}", direction_name(direction), self.x),
+            FlightPattern::Cruiser => format!("cruiser {}", self.y),
+            FlightPattern::SineWave { amplitude, period, .. } => format!("sine {} {} {}", self.y, amplitude, period),
+            FlightPattern::Banking { direction, turn_y, .. } => format!("banking {} {} {}", direction_name(direction), self.x, turn_y),
+            FlightPattern::Heavy(direction) => format!("heavy {} {}", direction_name(direction), self.x),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Grid {
+    Ground,
+    Walls,
+    Tilled,
+}
+
+impl Grid {
+    fn name(self) -> &'static str {
+        match self {
+            Grid::Ground => "ground",
+            Grid::Walls => "walls",
+            Grid::Tilled => "tilled",
+        }
+    }
+}
+
+impl Level {
+    /// A flat level `width` tiles wide with no planes, finishing at its last column
+    pub fn new(width: usize) -> Self {
+        Self {
+            name: String::from("Untitled"),
+            width,
+            tiles: vec![LevelTile::default(); width * HEIGHT],
+            spawns: Vec::new(),
+            finish: width as u32,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, LevelError> {
+        let contents = fs::read_to_string(path).map_err(LevelError::Io)?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, LevelError> {
+        let mut name = String::new();
+        let mut finish = None;
+        let mut spawns = Vec::new();
+        // Each grid's keyword line and its rows, rows keep their line and column for errors
+        let mut grids: Vec<(Grid, usize, Vec<(usize, usize, &str)>)> = Vec::new();
+        let mut in_grid = false;
+        let mut line_count = 0;
+
+        for (i, line) in contents.lines().enumerate() {
+            let line_no = i + 1;
+            line_count = line_no;
+            let words = words(line);
+
+            if in_grid {
+                if let Some((_, _, rows)) = grids.last_mut() {
+                    if rows.len() < HEIGHT {
+                        match words.as_slice() {
+                            [(column, row)] => rows.push((line_no, *column, *row)),
+                            [] => return syntax_error(line_no, 1, format!(
+                                "expected row {} of {}, grids can't contain blank lines or comments",
+                                rows.len() + 1, HEIGHT
+                            )),
+                            [_, (column, _), ..] => return syntax_error(line_no, *column, "grid rows can't contain spaces"),
+                        }
+                        continue;
+                    }
+                }
+                in_grid = false;
+            }
+
+            let mut words = words.into_iter();
+            let (column, keyword) = match words.next() {
+                Some(word) => word,
+                None => continue,
+            };
+            let mut args = Args {
+                line: line_no,
+                end: line.len() + 1,
+                words,
+            };
+
+            let grid = match keyword {
+                "name" => {
+                    let (start, _) = args.next_word("a name")?;
+                    name = line[start - 1..].trim().to_string();
+                    continue;
+                }
+                "finish" => {
+                    finish = Some(args.number("a distance")?);
+                    args.finish()?;
+                    continue;
+                }
+                "spawn" => {
+                    spawns.push(parse_spawn(&mut args)?);
+                    args.finish()?;
+                    continue;
+                }
+                "ground" => Grid::Ground,
+                "walls" => Grid::Walls,
+                "tilled" => Grid::Tilled,
+                _ => return syntax_error(line_no, column, format!("unknown keyword '{}'", keyword)),
+            };
+
+            args.finish()?;
+            if grids.iter().any(|(other, _, _)| *other == grid) {
+                return syntax_error(line_no, column, format!("'{}' grid given twice", keyword));
+            }
+            grids.push((grid, line_no, Vec::new()));
+            in_grid = true;
+        }
+
+        if let Some((_, line, rows)) = grids.iter().find(|(_, _, rows)| rows.len() < HEIGHT) {
+            return syntax_error(*line, 1, format!("expected {} rows, got {}", HEIGHT, rows.len()));
+        }
+        let width = match grids.iter().find(|(grid, _, _)| *grid == Grid::Ground) {
+            Some((_, _, rows)) => rows[0].2.chars().count(),
+            None => return syntax_error(line_count + 1, 1, "missing ground grid"),
+        };
+        let finish = match finish {
+            Some(finish) => finish,
+            None => return syntax_error(line_count + 1, 1, "missing finish distance"),
+        };
+
+        let mut tiles = vec![LevelTile::default(); width * HEIGHT];
+        for (grid, _, rows) in &grids {
+            for (r, &(line, start, row)) in rows.iter().enumerate() {
+                let row_width = row.chars().count();
+                if row_width != width {
+                    return syntax_error(line, start, format!("expected a row {} tiles wide, got {}", width, row_width));
+                }
+
+                for (q, c) in row.chars().enumerate() {
+                    let column = start + q;
+                    let tile = &mut tiles[r * width + q];
+                    match (grid, c) {
+                        (Grid::Ground, '0'..='9') => tile.ground = c as u8 - b'0',
+                        (Grid::Walls, '0'..='9') => tile.wall = c as u8 - b'0',
+                        (Grid::Walls, '.') | (Grid::Tilled, '.') => {}
+                        (Grid::Tilled, '#') => tile.tilled = true,
+                        (Grid::Tilled, '*') => {
+                            tile.tilled = true;
+                            tile.grown = true;
+                        }
+                        _ => return syntax_error(line, column, format!("unexpected '{}' in {} grid", c, grid.name())),
+                    }
+
+                    let height = tile.ground.max(tile.wall);
+                    if height > MAX_BRICK_HEIGHT {
+                        return syntax_error(line, column, format!("heights can be at most {}, got {}", MAX_BRICK_HEIGHT, height));
+                    }
+                }
+            }
+        }
+
+        spawns.sort_by_key(|spawn| spawn.frame);
+
+        Ok(Self {
+            name,
+            width,
+            tiles,
+            spawns,
+            finish,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// The level in the same format `parse` reads
+    pub fn to_text(&self) -> String {
+        // `parse` wants a word after `name`, an unnamed level leaves the line out
+        let mut out = String::new();
+        if !self.name.trim().is_empty() {
+            out.push_str(&format!("name {}\n", self.name.trim()));
+        }
+        out.push_str(&format!("finish {}\n\n", self.finish));
+        for spawn in &self.spawns {
+            out.push_str(&format!("spawn {} {}\n", spawn.frame, spawn.describe()));
+        }
+
+        let grids: [(&str, fn(&LevelTile) -> char); 3] = [
+            ("ground", |tile| (b'0' + tile.ground) as char),
+            ("walls", |tile| if tile.wall > tile.ground { (b'0' + tile.wall) as char } else { '.' }),
+            ("tilled", |tile| match (tile.tilled, tile.grown) {
+                (_, true) => '*',
+                (true, false) => '#',
+                (false, false) => '.',
+            }),
+        ];
+        for (keyword, symbol) in grids.iter() {
+            out.push_str(&format!("\n{}\n", keyword));
+            for row in self.tiles.chunks(self.width.max(1)) {
+                out.extend(row.iter().map(symbol));
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    /// The tile at `hex`, the level is widened to fit it if needed
+    pub fn tile_mut(&mut self, hex: Axial) -> Option<&mut LevelTile> {
+        let (col, row) = grid_position(hex);
+        if col < 0 || row < 0 || row >= HEIGHT as i32 {
+            return None;
+        }
+
+        let col = col as usize;
+        if col >= self.width {
+            let width = col + 1;
+            let mut tiles = vec![LevelTile::default(); width * HEIGHT];
+            for (r, row) in self.tiles.chunks(self.width.max(1)).enumerate() {
+                tiles[r * width..r * width + row.len()].copy_from_slice(row);
+            }
+            self.tiles = tiles;
+            self.width = width;
+        }
+        self.tiles.get_mut(row as usize * self.width + col)
+    }
+
+    pub fn generator(&self) -> LevelGenerator {
+        LevelGenerator {
+            width: self.width,
+            tiles: self.tiles.clone(),
+        }
+    }
+}
+
+/// The level grid column and row drawn at `hex`
+pub fn grid_position(hex: Axial) -> (i32, i32) {
+    let Offset { col, row } = Offset::from(hex);
+    (col - LEVEL_FIRST_COLUMN, row)
+}
+
+/// Builds `HexMap` chunks out of a level's tiles, the level's columns and rows
+/// are `Offset` coords shifted right by `LEVEL_FIRST_COLUMN`
+pub struct LevelGenerator {
+    width: usize,
+    tiles: Vec<LevelTile>,
+}
+
+impl MapGenerator for LevelGenerator {
+    fn generate(&self, _seed: u64, index: u64, height: usize, _max_floor_height: u8) -> HexChunk {
+        let mut tiles = ChunkTiles::new(index, height);
+        let hexes: Vec<Axial> = tiles.hexes().collect();
+        for hex in hexes {
+            let (col, row) = grid_position(hex);
+            if col < 0 || col as usize >= self.width || row < 0 || row as usize >= HEIGHT {
+                continue;
+            }
+
+            let level_tile = self.tiles[row as usize * self.width + col as usize];
+            if let Some(tile) = tiles.get_mut(hex) {
+                tile.ground_height = level_tile.ground;
+                tile.wall_height = level_tile.wall.max(level_tile.ground);
+                tile.is_tilled = level_tile.tilled;
+                tile.is_grown = level_tile.grown;
+            }
+        }
+        tiles.into_chunk()
+    }
+}
+
+/// The hand-authored level a run is on and how far through its spawns it is,
+/// empty for procedural runs
+#[derive(Default)]
+pub struct LevelState {
+    pub level: Option<Level>,
+    /// Index into the level's spawns of the next plane to spawn
+    pub next_spawn: usize,
+    /// Frames since the run started
+    pub frame: u64,
+}
+
+impl LevelState {
+    pub fn new(level: &Level) -> Self {
+        Self {
+            level: Some(level.clone()),
+            next_spawn: 0,
+            frame: 0,
+        }
+    }
+
+    /// Levels spawn their own planes instead of using `platform_spawner`
+    pub fn is_scripted(&self) -> bool {
+        self.level.is_some()
+    }
+}
+
+/// Spawns every plane the level has scheduled for this frame
+pub fn scripted_spawner(all_storages: AllStoragesViewMut) {
+    let due = all_storages.run(|mut state: UniqueViewMut<LevelState>| {
+        let state = &mut *state;
+        let spawns = match &state.level {
+            Some(level) => &level.spawns,
+            None => return Vec::new(),
+        };
+
+        let mut due = Vec::new();
+        while let Some(spawn) = spawns.get(state.next_spawn) {
+            if spawn.frame > state.frame {
+                break;
+            }
+            due.push(*spawn);
+            state.next_spawn += 1;
+        }
+        state.frame += 1;
+        due
+    });
+
+    for spawn in due {
+        systems::spawn_plane(&all_storages, spawn.x as f64, spawn.y as f64, Plane::new(spawn.pattern));
+    }
+}
+
+/// Ends the run once the player gets past the level's finish line
+pub fn level_finish(state: UniqueView<LevelState>, map: UniqueView<HexMap>, mut landing: UniqueViewMut<Landing>) {
+    if let Some(level) = &state.level {
+        if landing.death.is_none() && map.distance() >= level.finish {
+            landing.finished = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A grid of `HEIGHT` rows, `replace` swaps one of them out
+    fn grid(keyword: &str, row: &str, replace: Option<(usize, &str)>) -> String {
+        let mut out = format!("{}\n", keyword);
+        for r in 0..HEIGHT {
+            match replace {
+                Some((at, other)) if at == r => out.push_str(other),
+                _ => out.push_str(row),
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn error_at(text: &str) -> (usize, usize, String) {
+        match Level::parse(text) {
+            Err(LevelError::Syntax { line, column, message }) => (line, column, message),
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_a_level() {
+        let text = format!(
+            "name Test\nfinish 5\nspawn 30 cruiser 300\nspawn 10 diagonal up 900\n{}{}",
+            grid("ground", "01234", None),
+            grid("tilled", "..#*.", None),
+        );
+        let level = Level::parse(&text).unwrap();
+
+        assert_eq!(level.name, "Test");
+        assert_eq!(level.width, 5);
+        assert_eq!(level.finish, 5);
+        assert_eq!(level.spawns.iter().map(|spawn| spawn.frame).collect::<Vec<_>>(), vec![10, 30]);
+        assert_eq!(level.tiles[4].ground, 4);
+        assert!(level.tiles[2].tilled && !level.tiles[2].grown);
+        assert!(level.tiles[3].tilled && level.tiles[3].grown);
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let text = format!(
+            "name Round trip\nfinish 4\nspawn 10 diagonal up 900\n{}{}{}",
+            grid("ground", "0123", None),
+            grid("walls", ".3..", None),
+            grid("tilled", ".#*.", None),
+        );
+        let level = Level::parse(&text).unwrap();
+        let parsed = Level::parse(&level.to_text()).unwrap();
+
+        assert_eq!(parsed.name, "Round trip");
+        assert_eq!(parsed.to_text(), level.to_text());
+    }
+
+    #[test]
+    fn unnamed_level_round_trips() {
+        let mut level = Level::new(3);
+        level.name.clear();
+
+        let parsed = Level::parse(&level.to_text()).unwrap();
+        assert_eq!(parsed.name, "");
+        assert_eq!(parsed.width, 3);
+        assert_eq!(parsed.finish, 3);
+    }
+
+    #[test]
+    fn unknown_keyword() {
+        let (line, column, message) = error_at("finish 5\n  bogus 3\n");
+        assert_eq!((line, column), (2, 3));
+        assert!(message.contains("unknown keyword 'bogus'"), "{}", message);
+    }
+
+    #[test]
+    fn short_grid() {
+        let mut text = String::from("finish 5\nground\n");
+        for _ in 0..HEIGHT - 1 {
+            text.push_str("00000\n");
+        }
+        let (line, column, message) = error_at(&text);
+        assert_eq!((line, column), (2, 1));
+        assert!(message.contains(&format!("got {}", HEIGHT - 1)), "{}", message);
+    }
+
+    #[test]
+    fn ragged_row() {
+        let text = format!("finish 5\n{}", grid("ground", "00000", Some((3, "  0000"))));
+        let (line, column, message) = error_at(&text);
+        assert_eq!((line, column), (6, 3));
+        assert!(message.contains("5 tiles wide, got 4"), "{}", message);
+    }
+
+    #[test]
+    fn non_ascii_row_width_counts_tiles() {
+        let text = format!("finish 5\n{}", grid("ground", "00000", Some((3, "00é00"))));
+        let (line, column, message) = error_at(&text);
+        assert_eq!((line, column), (6, 3));
+        assert!(message.contains("unexpected 'é' in ground grid"), "{}", message);
+    }
+
+    #[test]
+    fn bad_digit() {
+        let text = format!("finish 5\n{}", grid("ground", "00000", Some((0, "00x00"))));
+        let (line, column, message) = error_at(&text);
+        assert_eq!((line, column), (3, 3));
+        assert!(message.contains("unexpected 'x' in ground grid"), "{}", message);
+    }
+
+    #[test]
+    fn missing_finish() {
+        let text = grid("ground", "00000", None);
+        let (line, column, message) = error_at(&text);
+        assert_eq!((line, column), (HEIGHT + 2, 1));
+        assert!(message.contains("missing finish"), "{}", message);
+    }
+
+    #[test]
+    fn bad_spawn_argument() {
+        let text = format!("finish 5\nspawn 10 cruiser abc\n{}", grid("ground", "00000", None));
+        let (line, column, message) = error_at(&text);
+        assert_eq!((line, column), (2, 18));
+        assert!(message.contains("expected a y position, got 'abc'"), "{}", message);
+    }
+
+    #[test]
+    fn comment_inside_grid() {
+        let text = format!("finish 5\n{}", grid("ground", "00000", Some((2, "# not a row"))));
+        let (line, column, message) = error_at(&text);
+        assert_eq!((line, column), (5, 1));
+        assert!(message.contains("expected row 3 of"), "{}", message);
+        assert!(message.contains("blank lines or comments"), "{}", message);
+    }
+
+    #[test]
+    fn blank_line_inside_grid() {
+        let text = format!("finish 5\n{}", grid("ground", "00000", Some((4, ""))));
+        let (line, column, message) = error_at(&text);
+        assert_eq!((line, column), (7, 1));
+        assert!(message.contains("blank lines or comments"), "{}", message);
+    }
+
+    #[test]
+    fn columns_line_up_on_screen() {
+        let mut level = Level::new(10);
+        let hex = Axial::from(Offset { col: LEVEL_FIRST_COLUMN + 3, row: 5 });
+        level.tile_mut(hex).unwrap().ground = 2;
+        assert_eq!(level.tiles[5 * level.width + 3].ground, 2);
+
+        let map = HexMap::with_generator(HEIGHT, 0, MAX_FLOOR_HEIGHT, Box::new(level.generator()));
+        assert_eq!(map.get(hex).unwrap().ground_height, 2);
+        assert_eq!(map.get(Offset { col: LEVEL_FIRST_COLUMN + 3, row: 4 }).unwrap().ground_height, 0);
+    }
+
+    #[test]
+    fn bottom_left_corner_is_generated() {
+        let text = format!("finish 5\n{}{}", grid("ground", "00000", None), grid("walls", ".....", Some((HEIGHT - 1, "3...."))));
+        let level = Level::parse(&text).unwrap();
+
+        let map = HexMap::with_generator(HEIGHT, 0, MAX_FLOOR_HEIGHT, Box::new(level.generator()));
+        let corner = Axial::from(Offset { col: LEVEL_FIRST_COLUMN, row: HEIGHT as i32 - 1 });
+        assert!(corner.q >= 0);
+        assert_eq!(map.get(corner).unwrap().wall_height, 3);
+    }
+}
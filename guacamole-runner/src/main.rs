@@ -2,13 +2,42 @@ mod systems;
 #[allow(dead_code)]
 mod consts;
 mod map;
+mod hex;
+mod terrain;
+mod biomes;
 mod components;
+mod input;
+mod simulation;
+mod replay;
+mod highscores;
+mod menu;
+mod pause;
+mod config;
+mod difficulty;
+mod collision;
+mod spatial;
+mod scoring;
+mod powerups;
+mod level;
+mod editor;
+mod savegame;
+
+use std::{
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::SystemTime,
+};
 
 use components::{
-    Player,
-    Height,
-    Collider,
     Points,
+    Textures,
+    RunOutcome,
+};
+
+use input::{
+    InputFrame,
 };
 
 use map::{
@@ -16,6 +45,56 @@ use map::{
     HexMap,
 };
 
+use replay::{
+    InputSource,
+    Replay,
+};
+
+use scoring::{
+    Scoring,
+    ScoreBreakdown,
+};
+
+use powerups::{
+    ActivePowerUps,
+};
+
+use highscores::{
+    HighScores,
+    Score,
+};
+
+use menu::{
+    MenuState,
+    Settings,
+};
+
+use pause::{
+    PauseChoice,
+    PauseState,
+};
+
+use config::{
+    self,
+    ConfigWatcher,
+    GameConfig,
+    DEFAULT_CONFIG_PATH,
+};
+
+use difficulty::{
+    Difficulty,
+    DifficultyPreset,
+};
+
+use level::{
+    Level,
+};
+
+use savegame::{
+    SaveGame,
+    SaveError,
+};
+
 use consts::*;
 
 use vermarine_lib::{
@@ -24,18 +103,14 @@ use vermarine_lib::{
         RenderingWorkloadSystems,
         draw_buffer::{
             DrawBuffer,
-            DrawCommand,
         },
         Drawables,
-        Sprite,
-    },
-    components::{
-        Transform,
     },
     tetra::{
         self,
         ContextBuilder,
         Context,
+        Event,
         graphics::{
             Color,
             Camera,
@@ -49,7 +124,6 @@ use vermarine_lib::{
         },
         input::{
             self,
-            InputContext,
             Key,
         },
         math::{
@@ -68,44 +142,276 @@ use vermarine_lib::{
     },
 };
 
-type Res = ();
+#[derive(Clone)]
+pub struct Res {
+    /// Seed passed with `--seed`, every run uses it instead of a random one
+    pub seed: Option<u64>,
+    /// Number of runs to simulate with `--headless` instead of opening a window
+    pub headless: Option<u32>,
+    /// Every run's input gets written to this file with `--record`, see `next_record_path`
+    pub record: Option<PathBuf>,
+    /// Runs recorded so far
+    pub records: u32,
+    /// Runs are played back from this file with `--replay`
+    pub replay: Option<PathBuf>,
+    pub settings: Settings,
+    /// Set by `PauseState` for the game underneath it to act on
+    pub pause_choice: Option<PauseChoice>,
+    /// Loaded from `--config` or `DEFAULT_CONFIG_PATH`
+    pub config: GameConfig,
+    pub config_path: PathBuf,
+    /// Modification time of the config file when `config` was read
+    pub config_modified: Option<SystemTime>,
+    /// Chosen from the menu or with `--difficulty`
+    pub difficulty: DifficultyPreset,
+    /// Hand-authored level loaded with `--level`, runs are procedural without one
+    pub level: Option<Level>,
+    /// Where `--level` points, the editor saves here
+    pub level_path: Option<PathBuf>,
+}
+
+impl Res {
+    pub fn from_args() -> Self {
+        let mut seed = None;
+        let mut headless = None;
+        let mut record = None;
+        let mut replay = None;
+        let mut config_path = PathBuf::from(DEFAULT_CONFIG_PATH);
+        let mut difficulty = DifficultyPreset::default();
+        let mut level = None;
+        let mut level_path = None;
+
+        let mut args = std::env::args().skip(1).peekable();
+        while let Some(arg) = args.next() {
+            if arg == "--seed" {
+                seed = args.next().and_then(|value| value.parse().ok());
+                if seed.is_none() {
+                    eprintln!("--seed expects an unsigned integer, using a random seed instead");
+                }
+            } else if arg == "--headless" {
+                // The run count is optional, only take the next argument if it is one
+                let runs = args.peek().and_then(|value| value.parse().ok());
+                if runs.is_some() {
+                    args.next();
+                }
+                headless = Some(runs.unwrap_or(1));
+            } else if arg == "--record" {
+                record = args.next().map(PathBuf::from);
+            } else if arg == "--replay" {
+                replay = args.next().map(PathBuf::from);
+            } else if arg == "--difficulty" {
+                match args.next().as_deref().and_then(DifficultyPreset::parse) {
+                    Some(preset) => difficulty = preset,
+                    None => eprintln!("--difficulty expects Easy, Normal or Hard, using {}", difficulty),
+                }
+            } else if arg == "--config" {
+                if let Some(path) = args.next() {
+                    config_path = PathBuf::from(path);
+                }
+            } else if arg == "--level" {
+                if let Some(path) = args.next() {
+                    match Level::load(Path::new(&path)) {
+                        Ok(loaded) => level = Some(loaded),
+                        Err(e) => eprintln!("{}: {}, using a generated map instead", path, e),
+                    }
+                    level_path = Some(PathBuf::from(path));
+                }
+            }
+        }
+
+        Self {
+            seed,
+            headless,
+            record,
+            records: 0,
+            replay,
+            settings: Settings::default(),
+            pause_choice: None,
+            // Read before loading so an edit made while loading is picked up later
+            config_modified: config::modified(&config_path),
+            config: GameConfig::load_or_default(&config_path),
+            config_path,
+            difficulty,
+            level,
+            level_path,
+        }
+    }
+
+    /// Where the next run gets recorded to, the first run uses the `--record`
+    /// path as is and later ones number it so earlier recordings are kept
+    pub fn next_record_path(&mut self) -> Option<PathBuf> {
+        let path = self.record.as_ref()?;
+        self.records += 1;
+        if self.records == 1 {
+            return Some(path.clone());
+        }
+
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut name = format!("{}-{}", stem, self.records);
+        if let Some(extension) = path.extension() {
+            name.push('.');
+            name.push_str(&extension.to_string_lossy());
+        }
+        Some(path.with_file_name(name))
+    }
+
+    /// Reads edits to the config file into `config`, returns true if any value
+    /// changed. Invalid edits are reported and the current values are kept.
+    /// `now` skips the watcher's wait between checks
+    pub fn reload_config(&mut self, watcher: &mut ConfigWatcher, now: bool) -> bool {
+        let reloaded = if now { watcher.check() } else { watcher.poll() };
+        self.config_modified = watcher.last_modified();
+
+        match reloaded {
+            Some(Ok(config)) => {
+                let changes = self.config.changes(&config);
+                if changes.is_empty() {
+                    return false;
+                }
+
+                println!("Reloaded {}", watcher.path().display());
+                for change in &changes {
+                    println!("    {}", change);
+                }
+                self.config = config;
+                true
+            }
+            Some(Err(e)) => {
+                eprintln!("{}: {}, keeping current values", watcher.path().display(), e);
+                false
+            }
+            None => false,
+        }
+    }
+}
 
 fn main() -> tetra::Result {
+    let res = Res::from_args();
+    if let Some(runs) = res.headless {
+        match &res.replay {
+            Some(path) => match Replay::load(path, &res.config) {
+                Ok(replay) => {
+                    let result = simulation::Simulation::play(&res.config, &replay);
+                    println!(
+                        "replay {}: {} points, distance {}, {} frames",
+                        path.display(), result.points, result.distance, result.frames
+                    );
+                }
+                Err(e) => eprintln!("Could not load replay {}: {}", path.display(), e),
+            },
+            None => simulation::run_headless(&res.config, res.difficulty, runs, res.seed),
+        }
+        return Ok(());
+    }
+
     ContextBuilder::new("Guacamole-Runner", 1280, 720)
         .show_mouse(true)
         .build()?
-        .run(|ctx| PushdownAutomaton::new(ctx, Game::new, |_| Ok(())))
+        .run(move |ctx| PushdownAutomaton::new(ctx, MenuState::new, move |_| Ok(res.clone())))
 }
 
 pub struct Game {
     world: World,
     background_canvas: Canvas,
+    /// Everything is drawn here first so `PauseState` can show the frozen game
+    frame_canvas: Canvas,
     text: Text,
+    input_source: InputSource,
+    config_watcher: ConfigWatcher,
+    /// Restarting plays the same level again
+    level: Option<Level>,
 }
 
 impl Game {
-    pub fn new(ctx: &mut Context, _: &mut Res) -> tetra::Result<Self> {
-        let world = World::new();
+    /// A run on the level from `--level`, or a generated map without one
+    pub fn new(ctx: &mut Context, res: &mut Res) -> tetra::Result<Self> {
+        let level = res.level.clone();
+        Self::with_level(ctx, res, level)
+    }
 
-        let text = Text::new("", Font::vector(ctx, "./assets/DejaVuSansMono.ttf", 16.0).unwrap());
+    pub fn with_level(ctx: &mut Context, res: &mut Res, level: Option<Level>) -> tetra::Result<Self> {
+        let config_watcher = Self::watch_config(res);
+        let mut map_seed = res.seed.unwrap_or_else(rand::random);
+        let mut spawn_seed = rand::random();
+        let mut difficulty = res.difficulty;
+        let mut level = level;
+        let mut input_source = match res.next_record_path() {
+            Some(path) => InputSource::Record(Replay::new(map_seed, spawn_seed, difficulty, &res.config, level.as_ref()), path),
+            None => InputSource::Live,
+        };
 
-        let mut game = Game {
-            world,
-            background_canvas: Canvas::new(ctx, 640, 360)
-                .expect("Could not make canvas"),
-            text,
+        if let Some(path) = &res.replay {
+            match Replay::load(path, &res.config) {
+                Ok(replay) => {
+                    map_seed = replay.map_seed;
+                    spawn_seed = replay.spawn_seed;
+                    difficulty = replay.difficulty;
+                    level = replay.level();
+                    input_source = InputSource::Playback(replay, 0);
+                }
+                Err(e) => eprintln!("Could not load replay {}: {}", path.display(), e),
+            }
+        }
+
+        let mut game = Self::empty(ctx, input_source, config_watcher, level);
+        game.init_world(ctx, &res.config, difficulty, map_seed, spawn_seed);
+
+        Ok(game)
+    }
+
+    /// Carries on a run saved from the pause menu, saves made with a different
+    /// config are refused as the rest of the run would play out differently
+    pub fn resume(ctx: &mut Context, res: &mut Res, save: SaveGame) -> Result<Self, SaveError> {
+        let config_watcher = Self::watch_config(res);
+        save.check_config(&res.config)?;
+
+        // Replays start from the beginning of a run so only runs recorded from their start can carry on recording
+        let input_source = match res.next_record_path() {
+            Some(path) => match save.recording.as_deref().map(Replay::parse) {
+                Some(Ok(replay)) => InputSource::Record(replay, path),
+                Some(Err(e)) => {
+                    eprintln!("Could not carry on the saved recording: {}", e);
+                    InputSource::Live
+                }
+                None => {
+                    eprintln!("The saved run wasn't recorded from its start so it won't be recorded");
+                    InputSource::Live
+                }
+            },
+            None => InputSource::Live,
         };
 
-        game.init_world(ctx);
+        let mut game = Self::empty(ctx, input_source, config_watcher, save.level());
+        game.init_world(ctx, &res.config, save.difficulty, save.map_seed, save.spawn_rng.seed);
+        save.restore(&game.world);
 
         Ok(game)
     }
 
-    fn init_world(&mut self, ctx: &mut Context) {
-        self.world.add_unique(map::HexMap::new(WIDTH, HEIGHT));
-        self.world.add_unique((*ctx.input_context()).clone());
-        self.world.add_unique(systems::SpawnTimer::new(70));
-        self.world.add_unique(Points::new());
+    /// A game with nothing in its world yet, `init_world` fills it in
+    fn empty(ctx: &mut Context, input_source: InputSource, config_watcher: ConfigWatcher, level: Option<Level>) -> Self {
+        Game {
+            world: World::new(),
+            background_canvas: Canvas::new(ctx, 640, 360)
+                .expect("Could not make canvas"),
+            frame_canvas: Canvas::new(ctx, 1280, 720)
+                .expect("Could not make canvas"),
+            text: Text::new("", Font::vector(ctx, "./assets/DejaVuSansMono.ttf", 16.0).unwrap()),
+            input_source,
+            config_watcher,
+            level,
+        }
+    }
+
+    /// Starts watching the config file, edits made since it was last read, like
+    /// while in the menus, are read first so the run starts with them
+    fn watch_config(res: &mut Res) -> ConfigWatcher {
+        let mut watcher = ConfigWatcher::new(res.config_path.clone(), res.config_modified);
+        res.reload_config(&mut watcher, true);
+        watcher
+    }
+
+    fn init_world(&mut self, ctx: &mut Context, config: &GameConfig, difficulty: DifficultyPreset, map_seed: u64, spawn_seed: u64) {
         self.world.add_unique_non_send_sync(Drawables::new(ctx).unwrap());
 
         self.world
@@ -117,26 +423,35 @@ impl Game {
             camera.position = Vec2::new(640., 360.);
         });
 
-        let (player_tex, _) = self.world.run(|drawables: NonSendSync<UniqueView<Drawables>>| {
-            (
-                drawables.alias[textures::PLAYER],
-                drawables.alias[textures::AEROPLANE],
-            )
+        let textures = self.world.run(|drawables: NonSendSync<UniqueView<Drawables>>| {
+            Textures {
+                player: drawables.alias[textures::PLAYER],
+                aeroplane: drawables.alias[textures::AEROPLANE],
+                marker: drawables.alias[textures::MARKER],
+            }
         });
 
-        self.world
-            .entity_builder()
-            .with(Sprite::from_command(
-                DrawCommand::new(player_tex)
-                .scale(Vec2::new(3., 3.))
-                .draw_layer(draw_layers::PLAYER)
-                .origin(Vec2::new(20., 18.))
-            ))
-            .with(Transform::new(200., 360.))
-            .with(Player {})
-            .with(Collider::new(-20 * 3, -8 * 3, 36 * 3, 16 * 3))
-            .with(Height(START_HEIGHT))
-            .build();
+        simulation::init_gameplay(&self.world, config, difficulty, map_seed, spawn_seed, textures, self.level.as_ref());
+    }
+
+    /// Applies edits to the config file to the running game. Runs being
+    /// recorded or played back keep the config they started with until the next run
+    fn reload_config(&mut self, res: &mut Res) {
+        if !res.reload_config(&mut self.config_watcher, false) {
+            return;
+        }
+
+        if self.input_source.is_live() {
+            simulation::apply_config(&self.world, &res.config);
+        } else {
+            println!("    not applied until the next run while a replay is recording or playing");
+        }
+    }
+
+    fn capture(&self) -> SaveGame {
+        let mut save = SaveGame::capture(&self.world);
+        save.recording = self.input_source.recording();
+        save
     }
 
     fn draw_background(&mut self, ctx: &mut Context) {
@@ -146,7 +461,7 @@ impl Game {
         self.world.run(render_hex_map);
         self.world.run_with_data(DrawBuffer::flush, ctx);
         graphics::flush(ctx);
-        graphics::reset_canvas(ctx);
+        graphics::set_canvas(ctx, &self.frame_canvas);
 
         graphics::clear(ctx, CLEAR_COL);
 
@@ -158,35 +473,53 @@ impl Game {
     }
 }
 
+/// However the run is left its recording gets written, `finish` makes sure it only happens once
+impl Drop for Game {
+    fn drop(&mut self) {
+        self.input_source.finish();
+    }
+}
+
 impl PDAState<Res> for Game {
-    fn update(&mut self, ctx: &mut Context, _res: &mut Res) -> tetra::Result<Trans<Res>> {
-        let input_ctx = ctx.input_context();
-        self.world.run(|mut ctx: UniqueViewMut<InputContext>| {
-            *ctx = (*input_ctx).clone();
-        });
+    fn update(&mut self, ctx: &mut Context, res: &mut Res) -> tetra::Result<Trans<Res>> {
+        match res.pause_choice.take() {
+            Some(PauseChoice::Restart) => return Ok(Trans::Switch(Box::new(Game::with_level(ctx, res, self.level.clone())?))),
+            Some(PauseChoice::SaveAndQuit) => match self.capture().save() {
+                Ok(()) => return Ok(Trans::Pop),
+                // Stay paused so the run isn't lost without the player knowing
+                Err(e) => {
+                    eprintln!("Could not save the run: {}", e);
+                    let message = format!("Could not save the run:\n{}", e);
+                    return Ok(Trans::Push(Box::new(PauseState::with_message(ctx, self.frame_canvas.clone(), message)?)));
+                }
+            },
+            Some(PauseChoice::QuitToMenu) => return Ok(Trans::Pop),
+            None => {}
+        }
 
-        self.world.run(systems::scroll_map);
-        self.world.run(systems::move_player);
-        self.world.run(systems::platform_spawner);
-        self.world.run(systems::move_planes);
-        self.world.run(systems::grow_ground);
-        self.world.run(systems::player_platform_check);
-        self.world.run(systems::player_height_visualiser);
-
-        let trans = self.world.run(|player: View<Player>, height: View<Height>| {
-            let (_, height) = (&player, &height).iter().next().unwrap();
-            if height.0 <= 0. {
-                let trans = self.world.run(|points: UniqueView<Points>, map: UniqueView<HexMap>| {
-                    let x = -map.position.x / FLOOR_WIDTH;
-                    let trans = Trans::Replace(Box::new(DeadState::new(ctx, points.0, x as u32).unwrap()));
-                    trans
-                });
-                trans
-            } else {
-                Trans::None
-            }
+        if input::is_key_pressed(ctx.input_context(), Key::Escape) {
+            return Ok(Trans::Push(Box::new(PauseState::new(ctx, self.frame_canvas.clone())?)));
+        }
+
+        self.reload_config(res);
+
+        let input = self.input_source.next_frame(InputFrame::from_input(ctx.input_context()));
+        self.world.run(|mut frame: UniqueViewMut<InputFrame>| {
+            *frame = input;
         });
 
+        simulation::run_gameplay(&self.world);
+
+        let trans = if let Some(outcome) = simulation::outcome(&self.world) {
+            self.input_source.finish();
+            let level = self.level.clone();
+            self.world.run(|scoring: UniqueView<Scoring>, map: UniqueView<HexMap>, difficulty: UniqueView<Difficulty>| {
+                Trans::Replace(Box::new(DeadState::new(ctx, outcome, &scoring.breakdown, map.distance(), map.seed, difficulty.preset, level).unwrap()))
+            })
+        } else {
+            Trans::None
+        };
+
         Ok(trans)
     }
 
@@ -205,32 +538,60 @@ impl PDAState<Res> for Game {
         self.world.run_workload("Rendering");
         self.world.run_with_data(DrawBuffer::flush, ctx);
 
-        self.world.run_with_data(|text: &mut Text, points: UniqueView<Points>| {
-            text.set_content(format!("Points: {}", points.0))
+        self.world.run_with_data(|text: &mut Text, points: UniqueView<Points>, scoring: UniqueView<Scoring>, powerups: UniqueView<ActivePowerUps>| {
+            text.set_content(format!(
+                "Points: {}\nCombo: x{}\nChain: x{:.1}\n{}",
+                points.0,
+                scoring.combo_multiplier(),
+                scoring.chain_multiplier(),
+                powerups.describe(),
+            ))
         }, &mut self.text);
         graphics::draw(ctx, &self.text, Vec2::new(40., 20.));
+        graphics::flush(ctx);
+        graphics::reset_canvas(ctx);
+
+        graphics::draw(ctx, &self.frame_canvas, Vec2::new(0., 0.));
 
+        let (live_planes, culled_planes) = self.world.run(|stats: UniqueView<systems::PlaneStats>| {
+            (stats.live, stats.culled)
+        });
         tetra::window::set_title(
             ctx,
             &format!(
-                "Guacamole-Runner - {:.0} FPS",
-                tetra::time::get_fps(ctx)
+                "Guacamole-Runner - {:.0} FPS - {} planes ({} culled)",
+                tetra::time::get_fps(ctx),
+                live_planes,
+                culled_planes,
             ),
         );
 
         Ok(())
     }
+
+    fn event(&mut self, ctx: &mut Context, _res: &mut Res, event: Event) -> tetra::Result<Trans<Res>> {
+        if let Event::FocusLost = event {
+            return Ok(Trans::Push(Box::new(PauseState::new(ctx, self.frame_canvas.clone())?)));
+        }
+
+        Ok(Trans::None)
+    }
 }
 
 
 struct DeadState {
     text: Text,
+    /// Played again on restart
+    level: Option<Level>,
 }
 
 impl PDAState<Res> for DeadState {
     fn update(&mut self, ctx: &mut Context, res: &mut Res) -> tetra::Result<Trans<Res>> {
         if input::is_key_down(ctx.input_context(), Key::Space) {
-            return Ok(Trans::Switch(Box::new(Game::new(ctx, res)?)));
+            return Ok(Trans::Switch(Box::new(Game::with_level(ctx, res, self.level.clone())?)));
+        }
+        if input::is_key_pressed(ctx.input_context(), Key::Escape) {
+            return Ok(Trans::Pop);
         }
 
         Ok(Trans::None)
@@ -238,24 +599,47 @@ impl PDAState<Res> for DeadState {
 
     fn draw(&mut self, ctx: &mut Context, _resources: &mut Res) -> tetra::Result {
         graphics::clear(ctx, Color::rgb(0.45, 0.65, 1.0));
-        graphics::draw(ctx, &self.text, Vec2::new(400., 300.));
+        graphics::draw(ctx, &self.text, Vec2::new(400., 80.));
 
         Ok(())
     }
 }
 
 impl DeadState {
-    pub fn new(ctx: &mut Context, points: u32, distance: u32) -> tetra::Result<Self> {
+    /// Runs on a level don't go on the high score table, they aren't
+    /// comparable with generated maps
+    pub fn new(ctx: &mut Context, outcome: RunOutcome, breakdown: &ScoreBreakdown, distance: u32, seed: u64, difficulty: DifficultyPreset, level: Option<Level>) -> tetra::Result<Self> {
+        let points = breakdown.total();
+        let mut high_scores = HighScores::load();
+        let rank = match &level {
+            Some(_) => None,
+            None => high_scores.insert(Score::new(points, distance, seed, difficulty)),
+        };
+        if rank.is_some() {
+            high_scores.save();
+        }
+        let map = match &level {
+            Some(level) => format!("Level: {}", level.name),
+            None => format!("Map seed: {}", seed),
+        };
+
         Ok(Self {
             text: Text::new(
 
 format!(
 "
+ {}
  You landed with {} points with a distance of {}
+{}
+               {}
         Press <SPACEBAR> to restart
-", points, distance),
+      Press <ESCAPE> to return to the menu
+
+            High Scores - {}
+{}", outcome.message(), points, distance, breakdown.describe(), map, difficulty, high_scores.table(difficulty, rank)),
                 Font::vector(ctx, "./assets/DejaVuSansMono.ttf", 16.0)?
-            )
+            ),
+            level,
         })
     }
 }
\ No newline at end of file
@@ -5,6 +5,18 @@ use crate::{
     consts::{
         *,
     },
+    hex::{
+        Axial,
+        Cube,
+        FractionalHex,
+    },
+    terrain::{
+        MapGenerator,
+    },
+    biomes::{
+        Biome,
+        BiomeSequencer,
+    },
     tetra::{
         math::{
             Vec3,
@@ -26,15 +38,29 @@ use vermarine_lib::{
     },
 };
 
-use rand::SeedableRng;
-use rand::Rng;
-use rand::rngs::StdRng;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use std::{
+    collections::VecDeque,
+    mem,
+    ops::{
+        Deref,
+        DerefMut,
+        Index,
+    },
+};
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct HexTileData {
     pub ground_height: u8,
     pub wall_height: u8,
     pub is_tilled: bool,
     pub is_grown: bool,
+    /// Picks the textures the tile is drawn with
+    pub biome: Biome,
 }
 
 impl HexTileData {
@@ -44,50 +70,47 @@ impl HexTileData {
             wall_height: height,
             is_tilled: false,
             is_grown: false,
+            biome: Biome::default(),
         }
     }
 }
 
-pub struct HexMap {
+/// Number of columns either side of the camera that get rendered
+const VIEW_RADIUS: i32 = 40;
+
+/// A `CHUNK_WIDTH` wide strip of the map, tiles are stored row by row
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HexChunk {
     pub tiles: Vec<HexTileData>,
-    pub width: usize,
+}
+
+/// An endless map made out of chunks that are generated ahead of the camera
+/// and dropped once they scroll off the left edge
+pub struct HexMap {
+    chunks: VecDeque<HexChunk>,
+    /// Index of the chunk at the front of `chunks`
+    first_chunk: u64,
     pub height: usize,
     pub position: Vec2<f32>,
-    pub tallest: u8,
+    /// Never lower than the wall height of any loaded tile
+    tallest: u8,
+    pub seed: u64,
+    pub max_floor_height: u8,
+    generator: Box<dyn MapGenerator + Send + Sync>,
 }
 
 impl HexMap {
-    pub fn new(width: usize, height: usize) -> Self {
-        let mut rand = StdRng::from_entropy();
-        let mut tiles = Vec::<HexTileData>::with_capacity(width * height);
-
-        let mut tallest = 0;
-        for _ in 0..width * height {
-            let value = rand.gen_range(0, MAX_FLOOR_HEIGHT + 1);
-            let tile = HexTileData::new(value);            
-            tiles.push(tile);
-            if value > tallest {
-                tallest = value;
-            }
-        }
+    #[allow(dead_code)]
+    pub fn new(height: usize, max_floor_height: u8) -> Self {
+        Self::with_seed(height, rand::random(), max_floor_height)
+    }
 
-        for _ in 0..5 {
-            for section in 0..(width / 10) {
-                let col = rand.gen_range(0, height + 1);
-                let mut total = 0;
-                for _ in 0..5 {
-                    total += rand.gen_range(3, 7 + 1);    
-                }
-                total /= 5;
-    
-                for offset in 0..total {
-                    if let Some(tile) = tiles.get_mut((col * width) + (section * 10) + offset) {
-                        tile.is_tilled = true;
-                    }
-                } 
-            }
-        }
+    /// Generates the same terrain and tilled strips for a given seed on every platform
+    pub fn with_seed(height: usize, seed: u64, max_floor_height: u8) -> Self {
+        Self::with_generator(height, seed, max_floor_height, Box::new(BiomeSequencer::default()))
+    }
 
+    pub fn with_generator(height: usize, seed: u64, max_floor_height: u8, generator: Box<dyn MapGenerator + Send + Sync>) -> Self {
         let height_px = {
             height as f32 * FLOOR_VERT_STEP
         };
@@ -96,16 +119,157 @@ impl HexMap {
             360. - height_px,
         );
         
-        HexMap {
-            tiles,
-            width,
+        let mut map = HexMap {
+            chunks: VecDeque::new(),
+            first_chunk: 0,
             height,
             position,
-            tallest,
+            tallest: 0,
+            seed,
+            max_floor_height,
+            generator,
+        };
+        map.stream_chunks();
+        map
+    }
+
+    /// Generates chunks the camera is about to reach and drops the ones behind it
+    pub fn stream_chunks(&mut self) {
+        let q = self.pixel_to_hex_raw(Vec2::zero(), 0.).q;
+        // Lower rows are shifted right so the leftmost visible column is further back
+        let first_visible = q as i32 - VIEW_RADIUS - self.height as i32;
+        let last_visible = q as i32 + VIEW_RADIUS;
+        let (loaded, first_chunk) = (self.chunks.len(), self.first_chunk);
+
+        while !self.chunks.is_empty() {
+            let end = self.start_column() + CHUNK_WIDTH as i32;
+            if end >= first_visible {
+                break;
+            }
+            self.chunks.pop_front();
+            self.first_chunk += 1;
+        }
+
+        while self.end_column() <= last_visible + CHUNK_WIDTH as i32 {
+            let index = self.first_chunk + self.chunks.len() as u64;
+            let chunk = self.generator.generate(self.seed, index, self.height, self.max_floor_height);
+            self.chunks.push_back(chunk);
+        }
+
+        if self.chunks.len() == loaded && self.first_chunk == first_chunk {
+            return;
         }
+
+        // Dropped chunks or lowered tiles might have held the tallest wall
+        self.recompute_tallest();
+    }
+
+    fn recompute_tallest(&mut self) {
+        self.tallest = self.chunks
+            .iter()
+            .flat_map(|chunk| chunk.tiles.iter())
+            .map(|tile| tile.wall_height)
+            .max()
+            .unwrap_or(0);
+    }
+
+    /// Index of the first loaded chunk and every loaded chunk, enough to put
+    /// the map back with `restore_chunks`
+    pub fn loaded_chunks(&self) -> (u64, impl Iterator<Item = &HexChunk>) {
+        (self.first_chunk, self.chunks.iter())
+    }
+
+    /// Swaps in chunks saved with `loaded_chunks`, chunks streamed in later
+    /// still come from this map's generator
+    pub fn restore_chunks(&mut self, first_chunk: u64, chunks: Vec<HexChunk>, position: Vec2<f32>) {
+        self.first_chunk = first_chunk;
+        self.chunks = chunks.into_iter().collect();
+        self.position = position;
+        self.recompute_tallest();
+        self.stream_chunks();
+    }
+
+    /// Height of the tallest loaded wall
+    pub fn tallest(&self) -> u8 {
+        self.tallest
+    }
+
+    /// How many tiles the map has scrolled by
+    pub fn distance(&self) -> u32 {
+        (-self.position.x / FLOOR_WIDTH) as u32
+    }
+
+    /// First column that is currently loaded
+    pub fn start_column(&self) -> i32 {
+        self.first_chunk as i32 * CHUNK_WIDTH as i32
+    }
+
+    /// One past the last column that is currently loaded
+    pub fn end_column(&self) -> i32 {
+        self.start_column() + (self.chunks.len() * CHUNK_WIDTH) as i32
+    }
+
+    /// Returns the tile at `hex` if it is loaded
+    pub fn get(&self, hex: impl Into<Axial>) -> Option<&HexTileData> {
+        let (chunk, index) = self.tile_index(hex.into())?;
+        self.chunks[chunk].tiles.get(index)
+    }
+
+    /// Returns the tile at `hex` if it is loaded, `tallest` is updated once the
+    /// returned guard is dropped
+    pub fn get_mut(&mut self, hex: impl Into<Axial>) -> Option<TileMut<'_>> {
+        let (chunk, index) = self.tile_index(hex.into())?;
+        let wall_height = self.chunks[chunk].tiles.get(index)?.wall_height;
+        Some(TileMut {
+            map: self,
+            chunk,
+            index,
+            wall_height,
+        })
+    }
+
+    /// Replaces the tile at `hex`, returns the old tile or None if `hex` isn't loaded
+    #[allow(dead_code)]
+    pub fn set(&mut self, hex: impl Into<Axial>, tile: HexTileData) -> Option<HexTileData> {
+        let mut current = self.get_mut(hex)?;
+        Some(mem::replace(&mut *current, tile))
+    }
+
+    /// Loaded tiles next to `hex`
+    #[allow(dead_code)]
+    pub fn neighbours(&self, hex: impl Into<Axial>) -> impl Iterator<Item = (Axial, &HexTileData)> {
+        let center = Cube::from(hex.into());
+        Cube::DIRECTIONS
+            .iter()
+            .map(move |&direction| Axial::from(center + direction))
+            .filter_map(move |hex| self.get(hex).map(|tile| (hex, tile)))
     }
 
-    pub fn pixel_to_hex_raw(&mut self, pos: Vec2<f32>, height_offset: f32) -> (f32, f32) {
+    /// Loaded tiles in `columns` and `rows` row by row, both ranges are inclusive
+    pub fn region(&self, columns: (i32, i32), rows: (i32, i32)) -> impl Iterator<Item = (Axial, &HexTileData)> {
+        let columns = columns.0.max(self.start_column())..=columns.1.min(self.end_column() - 1);
+        let rows = rows.0.max(0)..=rows.1.min(self.height as i32 - 1);
+        rows.flat_map(move |r| columns.clone().map(move |q| Axial::new(q, r)))
+            .filter_map(move |hex| self.get(hex).map(|tile| (hex, tile)))
+    }
+
+    /// Loaded tiles within `VIEW_RADIUS` of the camera, row by row
+    pub fn visible(&self) -> impl Iterator<Item = (Axial, &HexTileData)> {
+        let FractionalHex { q, r, .. } = self.pixel_to_hex_raw(Vec2::zero(), 0.);
+        let q = q as i32;
+        self.region((q - VIEW_RADIUS, q + VIEW_RADIUS), ((r - 20.) as i32, (r + 20.) as i32))
+    }
+
+    fn tile_index(&self, hex: Axial) -> Option<(usize, usize)> {
+        if hex.q < self.start_column() || hex.q >= self.end_column() || hex.r < 0 || hex.r >= self.height as i32 {
+            return None;
+        }
+
+        let x = (hex.q - self.start_column()) as usize;
+        Some((x / CHUNK_WIDTH, hex.r as usize * CHUNK_WIDTH + x % CHUNK_WIDTH))
+    }
+
+    pub fn pixel_to_hex_raw(&self, pos: Vec2<f32>, height_offset: f32) -> FractionalHex {
         let mut pos = pos;
         pos -= Vec2::new(18., 18.);
         pos.x -= self.position.x;
@@ -128,47 +292,39 @@ impl HexMap {
 
         let q: f32 = b0 * pos.x + b1 * pos.y;
         let r: f32 = b2 * pos.x + b3 * pos.y;
-        (q, r)
+        FractionalHex::new(q as f64, r as f64)
     }
 
-    /// Returns a hex in offset coords
-    #[allow(dead_code)]
-    pub fn pixel_to_hex(&mut self, pos: Vec2<f32>) -> Option<(i32, i32)> {
-        let mut tallest_height: Option<(u8, i32, i32)> = None;
+    /// Returns the tallest loaded hex drawn under `pos`
+    pub fn pixel_to_hex(&self, pos: Vec2<f32>) -> Option<Axial> {
+        let mut tallest_height: Option<(u8, Axial)> = None;
 
         for height in 0..=self.tallest {
             let height_offset = height as f32 * FLOOR_DEPTH_STEP;
 
-            let (q, r) = self.pixel_to_hex_raw(pos.clone(), height_offset);
-
-            let (q, r, s) = (q, r, -r -q);
-
-            let (x, y, _) = cube_round(q, r, s);
+            let hex = Axial::from(self.pixel_to_hex_raw(pos.clone(), height_offset).round());
     
-            if x < 0 || x >= self.width as i32 || y < 0 || y >= self.height as i32 {
-                continue;
-            }
-
-            let tile = &self.tiles[self.width * y as usize + x as usize];
-            let tile_height = tile.wall_height;
+            let tile_height = match self.get(hex) {
+                Some(tile) => tile.wall_height,
+                None => continue,
+            };
 
             if tile_height != height {
                 continue;
             }
             if tallest_height.is_none() || tile_height > tallest_height.unwrap().0 {
-                tallest_height = Some((tile_height, x, y));
+                tallest_height = Some((tile_height, hex));
             }
         }
 
-        if let Some((_, x, y)) = tallest_height {
-            return Some((x, y));
+        if let Some((_, hex)) = tallest_height {
+            return Some(hex);
         }
         None
     }
 
-    #[allow(dead_code)]
-    pub fn axial_to_pixel(&mut self, q: i32, r: i32) -> (f32, f32) {
-        let (q, r) = (q as f32, r as f32);
+    pub fn axial_to_pixel(&self, hex: Axial) -> (f32, f32) {
+        let (q, r) = (hex.q as f32, hex.r as f32);
 
         let size_x = FLOOR_WIDTH / f32::sqrt(3.0);
         // this value is derived by solving for X in:
@@ -187,68 +343,55 @@ impl HexMap {
     }
 }
 
-#[allow(dead_code)]
-pub fn cube_to_offset(q: i32, r: i32) -> (i32, i32) {
-    let col = q + (r - (r & 1)) / 2;
-    let row = r;
+/// Mutable access to a loaded tile from `HexMap::get_mut`
+pub struct TileMut<'a> {
+    map: &'a mut HexMap,
+    chunk: usize,
+    index: usize,
+    /// The tile's wall height before it was handed out
+    wall_height: u8,
+}
+
+impl Deref for TileMut<'_> {
+    type Target = HexTileData;
 
-    (col, row)
+    fn deref(&self) -> &HexTileData {
+        &self.map.chunks[self.chunk].tiles[self.index]
+    }
 }
 
-#[allow(dead_code)]
-pub fn offset_to_cube(off_x: i32, off_y: i32) -> (i32, i32, i32) {
-    let x = off_x - (off_y - (off_y as i32 & 1)) / 2;
-    let z = off_y;
-    let y = -x-z;
-    
-    (x, y, z)
+impl DerefMut for TileMut<'_> {
+    fn deref_mut(&mut self) -> &mut HexTileData {
+        &mut self.map.chunks[self.chunk].tiles[self.index]
+    }
 }
 
-pub fn cube_round(q: f32, r: f32, s: f32) -> (i32, i32, i32) {
-    let mut qi = q.round() as i32;
-    let mut ri = r.round() as i32;
-    let mut si = s.round() as i32;
+impl Drop for TileMut<'_> {
+    fn drop(&mut self) {
+        let wall_height = self.map.chunks[self.chunk].tiles[self.index].wall_height;
+        if wall_height > self.map.tallest {
+            self.map.tallest = wall_height;
+        } else if wall_height < self.wall_height && self.wall_height == self.map.tallest {
+            // Another loaded tile might be just as tall so only a full scan can tell
+            self.map.recompute_tallest();
+        }
+    }
+}
 
-    let q_diff = f64::abs(qi as f64 - q as f64);
-    let r_diff = f64::abs(ri as f64 - r as f64);
-    let s_diff = f64::abs(si as f64 - s as f64);
+/// Panics if the tile isn't loaded, use `get` when that can happen
+impl<H: Into<Axial>> Index<H> for HexMap {
+    type Output = HexTileData;
 
-    if q_diff > r_diff && q_diff > s_diff {
-        qi = -ri - si;
-    } else if r_diff > s_diff {
-        ri = -qi - si;
-    } else {
-        si = -qi - ri;
+    fn index(&self, hex: H) -> &HexTileData {
+        let hex = hex.into();
+        self.get(hex).unwrap_or_else(|| panic!("tile {:?} is not loaded", hex))
     }
-
-    (qi, ri, si)
 }
 
-pub fn render_hex_map(mut draw_buffer: UniqueViewMut<DrawBuffer>, drawables: NonSendSync<UniqueViewMut<Drawables>>, mut map: UniqueViewMut<HexMap>) {
+pub fn render_hex_map(mut draw_buffer: UniqueViewMut<DrawBuffer>, drawables: NonSendSync<UniqueViewMut<Drawables>>, map: UniqueView<HexMap>) {
     draw_buffer.new_command_pool(true);
     let command_pool = draw_buffer.get_command_pool();
 
-    let (q, r) = map.pixel_to_hex_raw(Vec2::zero(), 0.);
-
-    let startx = (q - 40.0)
-        .max(0.0).min(map.width as f32 - 1.0) as usize;
-    let endx = (q + 40.0)
-        .max(0.0).min(map.width as f32 - 1.0) as usize;
-    let starty = (r - 20.0)
-        .max(0.0).min(map.height as f32 - 1.0) as usize;
-    let endy = (r + 20.0)
-        .max(0.0).min(map.height as f32 - 1.0) as usize;
-
-    let (top_tex, wall_tex, brick_tex, brick_floor_tex, grown_tex, tilled_tex) = 
-        (
-            drawables.alias[textures::FLOOR], 
-            drawables.alias[textures::WALL], 
-            drawables.alias[textures::WALL_BRICK], 
-            drawables.alias[textures::FLOOR_BRICK],
-            drawables.alias[textures::FLOOR_GROWN],
-            drawables.alias[textures::FLOOR_TILLED],
-        );
-
     for height in 0..=MAX_BRICK_HEIGHT {
         let mut wall_buffer: Vec<DrawCommand> = Vec::with_capacity(1024);
         let mut wall_brick_buffer: Vec<DrawCommand> = Vec::with_capacity(1024);
@@ -256,47 +399,45 @@ pub fn render_hex_map(mut draw_buffer: UniqueViewMut<DrawBuffer>, drawables: Non
         let mut top_brick_buffer: Vec<DrawCommand> = Vec::with_capacity(1024);
         let mut top_tilled_buffer: Vec<DrawCommand> = Vec::with_capacity(1024);
         let mut top_grown_buffer: Vec<DrawCommand> = Vec::with_capacity(1024);
-        for y in starty..=endy {
-            for x in startx..=endx {
-                let tile = &map.tiles[map.width * y + x];
-                if tile.wall_height < height {
-                    continue;
-                }
-
-                let (draw_x, draw_y) = {
-                    let offset_x = (FLOOR_WIDTH / 2.0) * y as f32;
-                    let mut x = FLOOR_WIDTH * x as f32;
-                    x += offset_x;
-                    (
-                        x,
-                        (y as i32) as f32 * (FLOOR_VERT_STEP)
-                    )
-                };
-                let (draw_x, draw_y) =
-                    (
-                        draw_x + map.position.x,
-                        draw_y + map.position.y,
-                    );
-                
-                if height <= tile.ground_height && height != 0 {
-                    render_hex_walls(&mut wall_buffer, draw_x, draw_y, height, wall_tex);
-                }
-                else if height > tile.ground_height && height <= tile.wall_height {
-                    render_hex_bricks(&mut wall_brick_buffer, draw_x, draw_y, height, brick_tex);
-                }
-
-                if tile.is_grown && height == tile.ground_height {
-                    render_hex_top(&mut top_grown_buffer, draw_x, draw_y, tile.ground_height, grown_tex, Color::WHITE);
-                }
-                else if tile.is_tilled && height == tile.ground_height {
-                    render_hex_top(&mut top_tilled_buffer, draw_x, draw_y, tile.ground_height, tilled_tex, Color::WHITE);
-                }
-                else if height == tile.ground_height && height == tile.wall_height {
-                    render_hex_top(&mut top_buffer, draw_x, draw_y, tile.ground_height, top_tex, Color::WHITE);
-                }
-                else if height == tile.wall_height && height != tile.ground_height {
-                    render_hex_brick_top(&mut top_brick_buffer, draw_x, draw_y, tile.wall_height, brick_floor_tex, Color::WHITE);
-                }
+        for (hex, tile) in map.visible() {
+            if tile.wall_height < height {
+                continue;
+            }
+            let textures = tile.biome.textures();
+
+            let (draw_x, draw_y) = {
+                let offset_x = (FLOOR_WIDTH / 2.0) * hex.r as f32;
+                let mut x = FLOOR_WIDTH * hex.q as f32;
+                x += offset_x;
+                (
+                    x,
+                    hex.r as f32 * (FLOOR_VERT_STEP)
+                )
+            };
+            let (draw_x, draw_y) =
+                (
+                    draw_x + map.position.x,
+                    draw_y + map.position.y,
+                );
+            
+            if height <= tile.ground_height && height != 0 {
+                render_hex_walls(&mut wall_buffer, draw_x, draw_y, height, drawables.alias[textures.wall]);
+            }
+            else if height > tile.ground_height && height <= tile.wall_height {
+                render_hex_bricks(&mut wall_brick_buffer, draw_x, draw_y, height, drawables.alias[textures.wall_brick]);
+            }
+
+            if tile.is_grown && height == tile.ground_height {
+                render_hex_top(&mut top_grown_buffer, draw_x, draw_y, tile.ground_height, drawables.alias[textures.floor_grown], Color::WHITE);
+            }
+            else if tile.is_tilled && height == tile.ground_height {
+                render_hex_top(&mut top_tilled_buffer, draw_x, draw_y, tile.ground_height, drawables.alias[textures.floor_tilled], Color::WHITE);
+            }
+            else if height == tile.ground_height && height == tile.wall_height {
+                render_hex_top(&mut top_buffer, draw_x, draw_y, tile.ground_height, drawables.alias[textures.floor], textures.tint);
+            }
+            else if height == tile.wall_height && height != tile.ground_height {
+                render_hex_brick_top(&mut top_brick_buffer, draw_x, draw_y, tile.wall_height, drawables.alias[textures.floor_brick], Color::WHITE);
             }
         }
         command_pool.commands.extend(&wall_buffer);
@@ -309,19 +450,16 @@ pub fn render_hex_map(mut draw_buffer: UniqueViewMut<DrawBuffer>, drawables: Non
     
     // Draw dots at hex centers
     /*let marker_tex = drawables.alias[textures::MARKER];
-    for y_tile in starty..=endy {
-        for x_tile in startx..=endx {
-            let (x, y) = map.axial_to_pixel(x_tile as i32, y_tile as i32);
-            let tile = &map.tiles[map.width * y_tile + x_tile];
-
-            draw_buffer.draw(
-                DrawCommand::new(marker_tex)
-                    .position(Vec3::new(
-                        x - 2.0, y - 2.0, tile.wall_height as f32 * FLOOR_DEPTH_STEP 
-                    ))
-                    .draw_iso(true)
-            );
-        }
+    for (hex, tile) in map.visible() {
+        let (x, y) = map.axial_to_pixel(hex);
+
+        draw_buffer.draw(
+            DrawCommand::new(marker_tex)
+                .position(Vec3::new(
+                    x - 2.0, y - 2.0, tile.wall_height as f32 * FLOOR_DEPTH_STEP 
+                ))
+                .draw_iso(true)
+        );
     }*/
 
     draw_buffer.end_command_pool();
@@ -447,4 +585,74 @@ fn create_wall_brick_draw_cmd(x: f32, y: f32, height: f32, color: u8, texture: u
         .draw_layer(draw_layers::WALL)
         .draw_iso(true)
         .color(color)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(map: &HexMap) -> (u64, Vec<HexTileData>) {
+        let (first_chunk, chunks) = map.loaded_chunks();
+        (first_chunk, chunks.flat_map(|chunk| chunk.tiles.iter().cloned()).collect())
+    }
+
+    #[test]
+    fn same_seed_generates_same_chunks() {
+        let mut a = HexMap::with_seed(HEIGHT, 1234, 2);
+        let mut b = HexMap::with_seed(HEIGHT, 1234, 2);
+        assert_eq!(tiles(&a), tiles(&b));
+
+        // Chunks streamed in later have to match too
+        for map in [&mut a, &mut b].iter_mut() {
+            map.position.x -= CHUNK_WIDTH as f32 * FLOOR_WIDTH * 5.;
+            map.stream_chunks();
+        }
+        assert_eq!(tiles(&a), tiles(&b));
+    }
+
+    #[test]
+    fn different_seeds_generate_different_chunks() {
+        let a = HexMap::with_seed(HEIGHT, 1, 2);
+        let b = HexMap::with_seed(HEIGHT, 2, 2);
+        assert_ne!(tiles(&a), tiles(&b));
+    }
+
+    #[test]
+    fn set_replaces_loaded_tiles() {
+        let mut map = HexMap::with_seed(HEIGHT, 1234, 2);
+        let hex = Axial::new(map.start_column() + 3, 4);
+        let old = map[hex].clone();
+
+        assert_eq!(map.set(hex, HexTileData::new(5)), Some(old));
+        assert_eq!(map[hex], HexTileData::new(5));
+        assert!(map.tallest() >= 5);
+        assert_eq!(map.set(Axial::new(map.end_column(), 4), HexTileData::new(1)), None);
+    }
+
+    #[test]
+    fn neighbours_skip_unloaded_tiles() {
+        let map = HexMap::with_seed(HEIGHT, 1234, 2);
+        let inside = Axial::new(map.start_column() + 5, 5);
+        let neighbours: Vec<_> = map.neighbours(inside).map(|(hex, _)| hex).collect();
+        assert_eq!(neighbours.len(), 6);
+        assert!(neighbours.iter().all(|&hex| hex.distance(inside) == 1));
+
+        // The row above the top row isn't part of the map
+        let top = Axial::new(map.start_column() + 5, 0);
+        assert_eq!(map.neighbours(top).count(), 4);
+    }
+
+    #[test]
+    fn lowering_the_tallest_tile_lowers_tallest() {
+        let mut map = HexMap::with_seed(HEIGHT, 1234, 2);
+        let before = map.tallest();
+        let hex = Axial::new(map.start_column() + 3, 4);
+
+        map.get_mut(hex).unwrap().wall_height = before + 1;
+        assert_eq!(map.tallest(), before + 1);
+
+        map.get_mut(hex).unwrap().wall_height = 0;
+        let scanned = tiles(&map).1.iter().map(|tile| tile.wall_height).max().unwrap();
+        assert_eq!(map.tallest(), scanned);
+        assert!(map.tallest() <= before);
+    }
+}
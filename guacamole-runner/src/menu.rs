@@ -0,0 +1,420 @@
+use std::path::PathBuf;
+
+use crate::{
+    Game,
+    Res,
+    editor::{
+        EditorState,
+        DEFAULT_LEVEL_PATH,
+    },
+    level::{
+        Level,
+    },
+    consts::{
+        *,
+    },
+    highscores::{
+        HighScores,
+    },
+    difficulty::{
+        DifficultyPreset,
+    },
+    savegame::{
+        SaveGame,
+    },
+    tetra::{
+        self,
+        Context,
+        graphics::{
+            self,
+            Color,
+            text::{
+                Text,
+                Font,
+            },
+        },
+        input::{
+            self,
+            Key,
+        },
+        math::{
+            Vec2,
+        },
+    },
+};
+
+use vermarine_lib::{
+    pushdown_automaton_state::{
+        PDAState,
+        Trans,
+    },
+};
+
+const NUMBER_KEYS: [Key; 10] = [
+    Key::Num0,
+    Key::Num1,
+    Key::Num2,
+    Key::Num3,
+    Key::Num4,
+    Key::Num5,
+    Key::Num6,
+    Key::Num7,
+    Key::Num8,
+    Key::Num9,
+];
+
+/// Settings changed from the settings menu, these last until the game is closed
+#[derive(Clone)]
+pub struct Settings {
+    pub fullscreen: bool,
+    pub vsync: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            fullscreen: false,
+            vsync: true,
+        }
+    }
+}
+
+/// A vertical list of entries navigated with the arrow keys
+pub struct MenuList {
+    pub selected: usize,
+    len: usize,
+}
+
+impl MenuList {
+    pub fn new(len: usize) -> Self {
+        Self {
+            selected: 0,
+            len,
+        }
+    }
+
+    pub fn update(&mut self, ctx: &Context) {
+        self.update_enabled(ctx, |_| true);
+    }
+
+    /// Like `update` but skips over entries `enabled` rejects, the first entry must always be enabled
+    pub fn update_enabled(&mut self, ctx: &Context, enabled: impl Fn(usize) -> bool) {
+        let step = if input::is_key_pressed(ctx.input_context(), Key::Down) {
+            1
+        } else if input::is_key_pressed(ctx.input_context(), Key::Up) {
+            self.len - 1
+        } else {
+            0
+        };
+
+        if step != 0 {
+            self.selected = (self.selected + step) % self.len;
+            while !enabled(self.selected) {
+                self.selected = (self.selected + step) % self.len;
+            }
+        }
+        // The selected entry can become disabled while another state is on top
+        if !enabled(self.selected) {
+            self.selected = 0;
+        }
+    }
+
+    /// Lays the entries out one per line with the selected one marked
+    pub fn format(&self, entries: &[String]) -> String {
+        let mut out = String::new();
+        for (i, entry) in entries.iter().enumerate() {
+            let marker = if i == self.selected { ">" } else { " " };
+            out.push_str(&format!(" {} {}\n", marker, entry));
+        }
+        out
+    }
+}
+
+fn is_confirm_pressed(ctx: &Context) -> bool {
+    input::is_key_pressed(ctx.input_context(), Key::Enter) ||
+    input::is_key_pressed(ctx.input_context(), Key::Space)
+}
+
+/// The level the editor saves to and "Play Level" plays
+fn level_path(res: &Res) -> PathBuf {
+    res.level_path.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_LEVEL_PATH))
+}
+
+fn load_font(ctx: &mut Context) -> tetra::Result<Font> {
+    Font::vector(ctx, "./assets/DejaVuSansMono.ttf", 16.0)
+}
+
+const PLAY: usize = 0;
+const CONTINUE: usize = 1;
+const SEEDED_PLAY: usize = 2;
+const DIFFICULTY: usize = 3;
+const HIGH_SCORES: usize = 4;
+const PLAY_LEVEL: usize = 5;
+const LEVEL_EDITOR: usize = 6;
+const SETTINGS: usize = 7;
+const QUIT: usize = 8;
+
+pub struct MenuState {
+    text: Text,
+    list: MenuList,
+    /// Seed given on the command line, used by Play
+    default_seed: Option<u64>,
+    /// Digits typed in while Seeded Play is selected
+    seed_input: String,
+    /// Whether there is a suspended run, Continue can't be selected without one.
+    /// None until it is checked again after returning to the menu
+    has_save: Option<bool>,
+}
+
+impl MenuState {
+    pub fn new(ctx: &mut Context, res: &mut Res) -> tetra::Result<Self> {
+        Ok(Self {
+            text: Text::new("", load_font(ctx)?),
+            list: MenuList::new(9),
+            default_seed: res.seed,
+            seed_input: String::new(),
+            has_save: Some(SaveGame::exists()),
+        })
+    }
+
+    /// Acts on the selected entry once it is confirmed
+    fn select(&mut self, ctx: &mut Context, res: &mut Res) -> tetra::Result<Trans<Res>> {
+        match self.list.selected {
+            PLAY => {
+                res.seed = self.default_seed;
+                Ok(Trans::Push(Box::new(Game::new(ctx, res)?)))
+            }
+            // The save is only deleted once the run is back so a failed resume doesn't lose it
+            CONTINUE => match SaveGame::load().and_then(|save| Game::resume(ctx, res, save)) {
+                Ok(game) => {
+                    SaveGame::delete();
+                    Ok(Trans::Push(Box::new(game)))
+                }
+                Err(e) => {
+                    eprintln!("Could not continue the saved run: {}", e);
+                    Ok(Trans::None)
+                }
+            },
+            SEEDED_PLAY => match self.seed_input.parse() {
+                Ok(seed) => {
+                    res.seed = Some(seed);
+                    Ok(Trans::Push(Box::new(Game::new(ctx, res)?)))
+                }
+                Err(_) => Ok(Trans::None),
+            },
+            DIFFICULTY => {
+                res.difficulty = res.difficulty.next();
+                Ok(Trans::None)
+            }
+            HIGH_SCORES => Ok(Trans::Push(Box::new(HighScoresState::new(ctx, res.difficulty)?))),
+            PLAY_LEVEL => {
+                let path = level_path(res);
+                match Level::load(&path) {
+                    Ok(level) => Ok(Trans::Push(Box::new(Game::with_level(ctx, res, Some(level))?))),
+                    Err(e) => {
+                        eprintln!("{}: {}", path.display(), e);
+                        Ok(Trans::None)
+                    }
+                }
+            }
+            LEVEL_EDITOR => Ok(Trans::Push(Box::new(EditorState::new(ctx, res)?))),
+            SETTINGS => Ok(Trans::Push(Box::new(SettingsState::new(ctx)?))),
+            QUIT => {
+                tetra::window::quit(ctx);
+                Ok(Trans::None)
+            }
+            _ => Ok(Trans::None),
+        }
+    }
+
+    fn update_seed_input(&mut self, ctx: &Context) {
+        for (digit, key) in NUMBER_KEYS.iter().enumerate() {
+            if input::is_key_pressed(ctx.input_context(), *key) && self.seed_input.len() < 19 {
+                self.seed_input.push_str(&digit.to_string());
+            }
+        }
+        if input::is_key_pressed(ctx.input_context(), Key::Backspace) {
+            self.seed_input.pop();
+        }
+    }
+}
+
+impl PDAState<Res> for MenuState {
+    fn update(&mut self, ctx: &mut Context, res: &mut Res) -> tetra::Result<Trans<Res>> {
+        let has_save = *self.has_save.get_or_insert_with(SaveGame::exists);
+        self.list.update_enabled(ctx, |entry| entry != CONTINUE || has_save);
+        if self.list.selected == SEEDED_PLAY {
+            self.update_seed_input(ctx);
+        }
+        if self.list.selected == DIFFICULTY {
+            if input::is_key_pressed(ctx.input_context(), Key::Left) {
+                res.difficulty = res.difficulty.prev();
+            }
+            if input::is_key_pressed(ctx.input_context(), Key::Right) {
+                res.difficulty = res.difficulty.next();
+            }
+        }
+
+        if !is_confirm_pressed(ctx) {
+            return Ok(Trans::None);
+        }
+
+        let trans = self.select(ctx, res)?;
+        // Whatever was pushed can save or continue a run, look again once it's popped
+        if let Trans::Push(_) = &trans {
+            self.has_save = None;
+        }
+        Ok(trans)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, res: &mut Res) -> tetra::Result {
+        let seed = if self.seed_input.is_empty() {
+            String::from("type a seed")
+        } else {
+            self.seed_input.clone()
+        };
+
+        let entries = [
+            String::from("Play"),
+            String::from(if self.has_save == Some(true) { "Continue" } else { "Continue (no saved run)" }),
+            format!("Seeded Play: {}", seed),
+            format!("Difficulty: < {} >", res.difficulty),
+            String::from("High Scores"),
+            format!("Play Level: {}", level_path(res).display()),
+            String::from("Level Editor"),
+            String::from("Settings"),
+            String::from("Quit"),
+        ];
+
+        self.text.set_content(format!(
+"
+           Guacamole Runner
+
+{}
+ <UP>/<DOWN> to choose, <ENTER> to select
+", self.list.format(&entries)));
+
+        graphics::clear(ctx, CLEAR_COL);
+        graphics::draw(ctx, &self.text, Vec2::new(400., 200.));
+
+        Ok(())
+    }
+}
+
+pub struct HighScoresState {
+    text: Text,
+    high_scores: HighScores,
+    /// Whose table is shown, <LEFT>/<RIGHT> switch between them
+    difficulty: DifficultyPreset,
+}
+
+impl HighScoresState {
+    pub fn new(ctx: &mut Context, difficulty: DifficultyPreset) -> tetra::Result<Self> {
+        let mut state = Self {
+            text: Text::new("", load_font(ctx)?),
+            high_scores: HighScores::load(),
+            difficulty,
+        };
+        state.show_table();
+        Ok(state)
+    }
+
+    fn show_table(&mut self) {
+        let table = if self.high_scores.scores(self.difficulty).next().is_none() {
+            String::from(" No runs yet\n")
+        } else {
+            self.high_scores.table(self.difficulty, None)
+        };
+
+        self.text.set_content(format!(
+            "\n          High Scores: < {} >\n\n{}\n <LEFT>/<RIGHT> to change difficulty\n Press <ESCAPE> to go back\n",
+            self.difficulty, table
+        ));
+    }
+}
+
+impl PDAState<Res> for HighScoresState {
+    fn update(&mut self, ctx: &mut Context, _res: &mut Res) -> tetra::Result<Trans<Res>> {
+        if input::is_key_pressed(ctx.input_context(), Key::Escape) || is_confirm_pressed(ctx) {
+            return Ok(Trans::Pop);
+        }
+        if input::is_key_pressed(ctx.input_context(), Key::Left) {
+            self.difficulty = self.difficulty.prev();
+            self.show_table();
+        }
+        if input::is_key_pressed(ctx.input_context(), Key::Right) {
+            self.difficulty = self.difficulty.next();
+            self.show_table();
+        }
+
+        Ok(Trans::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _res: &mut Res) -> tetra::Result {
+        graphics::clear(ctx, CLEAR_COL);
+        graphics::draw(ctx, &self.text, Vec2::new(400., 150.));
+
+        Ok(())
+    }
+}
+
+const FULLSCREEN: usize = 0;
+const VSYNC: usize = 1;
+const BACK: usize = 2;
+
+pub struct SettingsState {
+    text: Text,
+    list: MenuList,
+}
+
+impl SettingsState {
+    pub fn new(ctx: &mut Context) -> tetra::Result<Self> {
+        Ok(Self {
+            text: Text::new("", load_font(ctx)?),
+            list: MenuList::new(3),
+        })
+    }
+}
+
+impl PDAState<Res> for SettingsState {
+    fn update(&mut self, ctx: &mut Context, res: &mut Res) -> tetra::Result<Trans<Res>> {
+        self.list.update(ctx);
+
+        if input::is_key_pressed(ctx.input_context(), Key::Escape) {
+            return Ok(Trans::Pop);
+        }
+
+        if is_confirm_pressed(ctx) {
+            match self.list.selected {
+                FULLSCREEN => {
+                    res.settings.fullscreen = !res.settings.fullscreen;
+                    tetra::window::set_fullscreen(ctx, res.settings.fullscreen)?;
+                }
+                VSYNC => {
+                    res.settings.vsync = !res.settings.vsync;
+                    tetra::window::set_vsync(ctx, res.settings.vsync)?;
+                }
+                BACK => return Ok(Trans::Pop),
+                _ => {}
+            }
+        }
+
+        Ok(Trans::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, res: &mut Res) -> tetra::Result {
+        let on_off = |value: bool| if value { "On" } else { "Off" };
+        let entries = [
+            format!("Fullscreen: {}", on_off(res.settings.fullscreen)),
+            format!("VSync: {}", on_off(res.settings.vsync)),
+            String::from("Back"),
+        ];
+
+        self.text.set_content(format!("\n               Settings\n\n{}", self.list.format(&entries)));
+
+        graphics::clear(ctx, Color::rgb(0.45, 0.65, 1.0));
+        graphics::draw(ctx, &self.text, Vec2::new(400., 200.));
+
+        Ok(())
+    }
+}
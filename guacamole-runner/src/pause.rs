@@ -0,0 +1,119 @@
+use crate::{
+    Res,
+    menu::{
+        MenuList,
+    },
+    tetra::{
+        self,
+        Context,
+        graphics::{
+            self,
+            Canvas,
+            Color,
+            DrawParams,
+            text::{
+                Text,
+                Font,
+            },
+        },
+        input::{
+            self,
+            Key,
+        },
+        math::{
+            Vec2,
+        },
+    },
+};
+
+use vermarine_lib::{
+    pushdown_automaton_state::{
+        PDAState,
+        Trans,
+    },
+};
+
+/// What `Game` should do once the pause state is popped off it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PauseChoice {
+    Restart,
+    /// Save the run so it can be carried on from the menu later
+    SaveAndQuit,
+    QuitToMenu,
+}
+
+const RESUME: usize = 0;
+const RESTART: usize = 1;
+const SAVE_AND_QUIT: usize = 2;
+const QUIT_TO_MENU: usize = 3;
+
+/// Pushed over `Game`, draws the last frame of the game dimmed underneath the menu
+pub struct PauseState {
+    frame: Canvas,
+    text: Text,
+    list: MenuList,
+    /// Shown under the menu, like why the run couldn't be saved
+    message: String,
+}
+
+impl PauseState {
+    /// `frame` is the canvas the game last drew to, it doesn't change while paused
+    pub fn new(ctx: &mut Context, frame: Canvas) -> tetra::Result<Self> {
+        Ok(Self {
+            frame,
+            text: Text::new("", Font::vector(ctx, "./assets/DejaVuSansMono.ttf", 16.0)?),
+            list: MenuList::new(4),
+            message: String::new(),
+        })
+    }
+
+    /// Pauses again showing `message`, the save and quit entry stays selected
+    pub fn with_message(ctx: &mut Context, frame: Canvas, message: String) -> tetra::Result<Self> {
+        let mut pause = Self::new(ctx, frame)?;
+        pause.list.selected = SAVE_AND_QUIT;
+        pause.message = message;
+        Ok(pause)
+    }
+}
+
+impl PDAState<Res> for PauseState {
+    fn update(&mut self, ctx: &mut Context, res: &mut Res) -> tetra::Result<Trans<Res>> {
+        self.list.update(ctx);
+
+        if input::is_key_pressed(ctx.input_context(), Key::Escape) {
+            return Ok(Trans::Pop);
+        }
+
+        if input::is_key_pressed(ctx.input_context(), Key::Enter) {
+            match self.list.selected {
+                RESUME => {}
+                RESTART => res.pause_choice = Some(PauseChoice::Restart),
+                SAVE_AND_QUIT => res.pause_choice = Some(PauseChoice::SaveAndQuit),
+                QUIT_TO_MENU => res.pause_choice = Some(PauseChoice::QuitToMenu),
+                _ => return Ok(Trans::None),
+            }
+            return Ok(Trans::Pop);
+        }
+
+        Ok(Trans::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, _res: &mut Res) -> tetra::Result {
+        let entries = [
+            String::from("Resume"),
+            String::from("Restart"),
+            String::from("Save and quit"),
+            String::from("Quit to menu"),
+        ];
+        self.text.set_content(format!("\n               Paused\n\n{}\n{}", self.list.format(&entries), self.message));
+
+        graphics::clear(ctx, Color::BLACK);
+        graphics::draw(ctx, &self.frame,
+            DrawParams::new()
+            .color(Color::rgba(0.4, 0.4, 0.4, 1.0))
+        );
+        graphics::draw(ctx, &self.text, Vec2::new(500., 250.));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+use crate::{
+    shipyard::{
+        *,
+    },
+    tetra::{
+        graphics::{
+            Color,
+        },
+        math::{
+            Vec2,
+        },
+    },
+    consts::{
+        *,
+    },
+    components::{
+        Player,
+        Plane,
+        Textures,
+    },
+    config::{
+        GameConfig,
+    },
+    difficulty::{
+        Difficulty,
+    },
+    collision::{
+        Collider,
+    },
+    spatial::{
+        SpatialHash,
+    },
+    systems::{
+        SpawnTimer,
+    },
+};
+
+use vermarine_lib::{
+    components::{
+        Transform,
+    },
+    rendering::{
+        Sprite,
+        draw_buffer::{
+            DrawCommand,
+        },
+    },
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use rand::{
+    Rng,
+    SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PowerUpKind {
+    /// Height runs out slower
+    Parachute,
+    /// Grows tiles further from the player
+    WideGrow,
+    /// Pulls nearby planes towards the player
+    Magnet,
+    /// Doubles points earned
+    ScoreDoubler,
+    /// Saves the player from one bad landing
+    Shield,
+}
+
+impl PowerUpKind {
+    pub const ALL: [PowerUpKind; 5] = [
+        PowerUpKind::Parachute,
+        PowerUpKind::WideGrow,
+        PowerUpKind::Magnet,
+        PowerUpKind::ScoreDoubler,
+        PowerUpKind::Shield,
+    ];
+
+    /// How many frames the effect lasts once picked up
+    pub fn duration(self) -> u32 {
+        match self {
+            PowerUpKind::Parachute => 60 * 8,
+            PowerUpKind::WideGrow => 60 * 10,
+            PowerUpKind::Magnet => 60 * 8,
+            PowerUpKind::ScoreDoubler => 60 * 10,
+            PowerUpKind::Shield => 60 * 30,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::Parachute => "Parachute",
+            PowerUpKind::WideGrow => "Wide planting",
+            PowerUpKind::Magnet => "Magnet",
+            PowerUpKind::ScoreDoubler => "Score x2",
+            PowerUpKind::Shield => "Shield",
+        }
+    }
+
+    /// Tint for the pickup's marker sprite
+    fn color(self) -> Color {
+        match self {
+            PowerUpKind::Parachute => Color::rgb(1.0, 1.0, 1.0),
+            PowerUpKind::WideGrow => Color::rgb(0.3, 0.9, 0.3),
+            PowerUpKind::Magnet => Color::rgb(0.9, 0.2, 0.2),
+            PowerUpKind::ScoreDoubler => Color::rgb(1.0, 0.85, 0.2),
+            PowerUpKind::Shield => Color::rgb(0.3, 0.6, 1.0),
+        }
+    }
+}
+
+/// A pickup waiting on the ground
+pub struct PowerUp(pub PowerUpKind);
+
+/// Frames left on each effect the player has picked up
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ActivePowerUps {
+    remaining: [u32; PowerUpKind::ALL.len()],
+}
+
+impl ActivePowerUps {
+    pub fn is_active(&self, kind: PowerUpKind) -> bool {
+        self.remaining[kind as usize] > 0
+    }
+
+    /// Picking up an effect that is already active restarts its timer
+    pub fn activate(&mut self, kind: PowerUpKind) {
+        self.remaining[kind as usize] = kind.duration();
+    }
+
+    /// Ends the effect early, returns whether it was active
+    pub fn consume(&mut self, kind: PowerUpKind) -> bool {
+        let active = self.is_active(kind);
+        self.remaining[kind as usize] = 0;
+        active
+    }
+
+    /// Active effects and their remaining frames, for the HUD
+    pub fn active(&self) -> impl Iterator<Item = (PowerUpKind, u32)> + '_ {
+        PowerUpKind::ALL
+            .iter()
+            .map(move |&kind| (kind, self.remaining[kind as usize]))
+            .filter(|(_, remaining)| *remaining > 0)
+    }
+
+    pub fn describe(&self) -> String {
+        self.active()
+            .map(|(kind, remaining)| format!("{} {}s\n", kind.name(), (remaining + 59) / 60))
+            .collect()
+    }
+
+    /// Tile radius `grow_ground` grows around the player
+    pub fn grow_radius(&self) -> i32 {
+        if self.is_active(PowerUpKind::WideGrow) {
+            WIDE_GROW_RADIUS
+        } else {
+            1
+        }
+    }
+
+    /// Multiplier on how fast the player's height runs out
+    pub fn fall_multiplier(&self) -> f32 {
+        if self.is_active(PowerUpKind::Parachute) {
+            PARACHUTE_FALL_MULTIPLIER
+        } else {
+            1.
+        }
+    }
+}
+
+/// Spawns pickups on their own timer and rng stream so they don't change which planes spawn
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PowerUpSpawner {
+    timer: SpawnTimer,
+    rng: ChaCha8Rng,
+}
+
+impl PowerUpSpawner {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(1);
+        Self {
+            timer: SpawnTimer::new(POWERUP_INTERVAL),
+            rng,
+        }
+    }
+}
+
+/// Covers the whole marker sprite, it is scaled along with the sprite
+fn powerup_collider() -> Collider {
+    Collider::rect(-MARKER_SIZE / 2., -MARKER_SIZE / 2., MARKER_SIZE, MARKER_SIZE)
+}
+
+pub fn powerup_spawner(all_storages: AllStoragesViewMut) {
+    let spawn = all_storages.run(|mut spawner: UniqueViewMut<PowerUpSpawner>| {
+        let spawner = &mut *spawner;
+        if spawner.timer.tick() {
+            let kind = PowerUpKind::ALL[spawner.rng.gen_range(0, PowerUpKind::ALL.len())];
+            let y = spawner.rng.gen_range(100, WINDOW_HEIGHT - 100);
+            Some((kind, y))
+        } else {
+            None
+        }
+    });
+
+    if let Some((kind, y)) = spawn {
+        spawn_powerup(&all_storages, (WINDOW_WIDTH + 36) as f64, y as f64, kind);
+    }
+}
+
+/// Builds a pickup entity, used by the spawner and when restoring a save
+pub fn spawn_powerup(all_storages: &AllStorages, x: f64, y: f64, kind: PowerUpKind) {
+    let tex = all_storages.run(|textures: UniqueView<Textures>| {
+        textures.marker
+    });
+
+    all_storages
+        .entity_builder()
+        .with(Transform::new(x, y))
+        .with(Sprite::from_command(
+            DrawCommand::new(tex)
+            .scale(Vec2::new(POWERUP_SCALE, POWERUP_SCALE))
+            .draw_layer(draw_layers::POWERUP)
+            .color(kind.color())
+            .origin(Vec2::new(MARKER_SIZE / 2., MARKER_SIZE / 2.))
+        ))
+        .with(PowerUp(kind))
+        .with(powerup_collider())
+        .build();
+}
+
+/// Pickups sit on the ground so they scroll with the map, which is drawn at twice its size
+pub fn move_powerups(config: UniqueView<GameConfig>, difficulty: UniqueView<Difficulty>, powerups: View<PowerUp>, mut transforms: ViewMut<Transform>) {
+    let scroll_rate = difficulty.scroll_rate(&config) as f64;
+    for (_, transform) in (&powerups, &mut transforms).iter() {
+        transform.x -= scroll_rate * 2.;
+    }
+}
+
+/// Deletes pickups that scrolled off the left edge
+pub fn cull_powerups(mut all_storages: AllStoragesViewMut) {
+    let off_screen = all_storages.run(|powerups: View<PowerUp>, transforms: View<Transform>, colliders: View<Collider>, sprites: View<Sprite>| {
+        (&powerups, &transforms, &colliders, &sprites)
+            .iter()
+            .with_id()
+            .filter(|(_, (_, transform, collider, sprite))| {
+                collider.world_shape(transform, Some(sprite)).bounds().max.x < 0.
+            })
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>()
+    });
+
+    for id in off_screen {
+        all_storages.delete(id);
+    }
+}
+
+/// Activates and deletes every pickup the player is touching
+pub fn collect_powerups(mut all_storages: AllStoragesViewMut) {
+    let collected = all_storages.run(|hash: UniqueView<SpatialHash>, player: View<Player>, powerups: View<PowerUp>, transforms: View<Transform>, colliders: View<Collider>, sprites: View<Sprite>, mut active: UniqueViewMut<ActivePowerUps>| {
+        let (_, transform, collider, sprite) = (&player, &transforms, &colliders, &sprites).iter().next().unwrap();
+        let shape = collider.world_shape(transform, Some(sprite));
+
+        let collected: Vec<EntityId> = hash.query(&shape).into_iter().filter(|&id| powerups.contains(id)).collect();
+        for &id in &collected {
+            active.activate(powerups[id].0);
+        }
+        collected
+    });
+
+    for id in collected {
+        all_storages.delete(id);
+    }
+}
+
+/// Pulls planes within `MAGNET_RADIUS` towards the player while the magnet is active
+pub fn magnet_planes(active: UniqueView<ActivePowerUps>, player: View<Player>, planes: View<Plane>, mut transforms: ViewMut<Transform>) {
+    if !active.is_active(PowerUpKind::Magnet) {
+        return;
+    }
+
+    let player_pos = match (&player, &transforms).iter().next() {
+        Some((_, transform)) => Vec2::new(transform.x, transform.y),
+        None => return,
+    };
+
+    for (_, transform) in (&planes, &mut transforms).iter() {
+        let delta = player_pos - Vec2::new(transform.x, transform.y);
+        let dist = (delta.x * delta.x + delta.y * delta.y).sqrt();
+        if dist > 0. && dist < MAGNET_RADIUS {
+            let pull = delta / dist * MAGNET_PULL.min(dist);
+            transform.x += pull.x;
+            transform.y += pull.y;
+        }
+    }
+}
+
+/// Counts down every active effect
+pub fn tick_powerups(mut active: UniqueViewMut<ActivePowerUps>) {
+    for remaining in active.remaining.iter_mut() {
+        *remaining = remaining.saturating_sub(1);
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    fs,
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use crate::{
+    input::InputFrame,
+    config::GameConfig,
+    difficulty::DifficultyPreset,
+    level::Level,
+};
+
+const HEADER: &str = "guacamole-replay 7";
+const FRAMES_PER_LINE: usize = 60;
+
+/// Everything needed to play a run back exactly, the seeds of both world rngs,
+/// the difficulty, the config's fingerprint, the level and the input for every frame
+pub struct Replay {
+    pub map_seed: u64,
+    pub spawn_seed: u64,
+    pub difficulty: DifficultyPreset,
+    /// `GameConfig::fingerprint` of the config the run was recorded with
+    pub config: u64,
+    /// The level played in the level file format, None for generated maps
+    pub level: Option<String>,
+    pub frames: Vec<InputFrame>,
+}
+
+impl Replay {
+    pub fn new(map_seed: u64, spawn_seed: u64, difficulty: DifficultyPreset, config: &GameConfig, level: Option<&Level>) -> Self {
+        Self {
+            map_seed,
+            spawn_seed,
+            difficulty,
+            config: config.fingerprint(),
+            level: level.map(Level::to_text),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// Loads a replay to be played with `config`, replays recorded with
+    /// different values would not play out the same so they are refused
+    pub fn load(path: &Path, config: &GameConfig) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        let replay = Self::parse(&contents)?;
+        if replay.config != config.fingerprint() {
+            return Err(invalid_data("recorded with a different game config"));
+        }
+        Ok(replay)
+    }
+
+    /// The level the run was on, `parse` already checked it parses
+    pub fn level(&self) -> Option<Level> {
+        self.level.as_deref().and_then(|text| Level::parse(text).ok())
+    }
+
+    /// Replay files are a header, the two seeds, the difficulty, the config
+    /// fingerprint, how many lines of level follow and then one hex digit per frame
+    pub fn to_text(&self) -> String {
+        let mut out = format!(
+            "{}\nmap_seed {}\nspawn_seed {}\ndifficulty {}\nconfig {:016x}\n",
+            HEADER, self.map_seed, self.spawn_seed, self.difficulty, self.config
+        );
+
+        let level = self.level.as_deref().unwrap_or("");
+        out.push_str(&format!("level {}\n", level.lines().count()));
+        for line in level.lines() {
+            out.push_str(line);
+            out.push('\n');
+        }
+
+        for line in self.frames.chunks(FRAMES_PER_LINE) {
+            for frame in line {
+                out.push_str(&format!("{:x}", frame.to_bits()));
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn parse(contents: &str) -> io::Result<Self> {
+        let mut lines = contents.lines();
+
+        if lines.next() != Some(HEADER) {
+            return Err(invalid_data("missing replay header"));
+        }
+        let map_seed = parse_seed(lines.next(), "map_seed")?;
+        let spawn_seed = parse_seed(lines.next(), "spawn_seed")?;
+        let difficulty = lines.next()
+            .and_then(|line| line.strip_prefix("difficulty "))
+            .and_then(|name| DifficultyPreset::parse(name.trim()))
+            .ok_or_else(|| invalid_data("missing or invalid difficulty"))?;
+        let config = lines.next()
+            .and_then(|line| line.strip_prefix("config "))
+            .and_then(|hash| u64::from_str_radix(hash.trim(), 16).ok())
+            .ok_or_else(|| invalid_data("missing or invalid config"))?;
+
+        let level_lines = lines.next()
+            .and_then(|line| line.strip_prefix("level "))
+            .and_then(|count| count.trim().parse::<usize>().ok())
+            .ok_or_else(|| invalid_data("missing or invalid level line count"))?;
+        let level = if level_lines > 0 {
+            let mut text = String::new();
+            for _ in 0..level_lines {
+                let line = lines.next().ok_or_else(|| invalid_data("level ends early"))?;
+                text.push_str(line);
+                text.push('\n');
+            }
+            Level::parse(&text).map_err(|e| invalid_data(&format!("level: {}", e)))?;
+            Some(text)
+        } else {
+            None
+        };
+
+        let mut frames = Vec::new();
+        for line in lines {
+            for c in line.trim().chars() {
+                let bits = c.to_digit(16)
+                    .ok_or_else(|| invalid_data(&format!("invalid frame '{}'", c)))?;
+                frames.push(InputFrame::from_bits(bits as u8));
+            }
+        }
+
+        Ok(Self {
+            map_seed,
+            spawn_seed,
+            difficulty,
+            config,
+            level,
+            frames,
+        })
+    }
+}
+
+fn parse_seed(line: Option<&str>, name: &str) -> io::Result<u64> {
+    let line = line.ok_or_else(|| invalid_data(&format!("missing {}", name)))?;
+    let mut parts = line.split_whitespace();
+    if parts.next() != Some(name) {
+        return Err(invalid_data(&format!("expected {}", name)));
+    }
+
+    parts.next()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| invalid_data(&format!("invalid {}", name)))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Where `Game` gets each frame's input from
+pub enum InputSource {
+    Live,
+    /// Live input that is also written to the path once the run ends or is left
+    Record(Replay, PathBuf),
+    /// Frames from a replay, the usize is the next frame to play
+    Playback(Replay, usize),
+}
+
+impl InputSource {
+    /// Returns the input for this frame, `live` is what the keyboard currently reads
+    pub fn next_frame(&mut self, live: InputFrame) -> InputFrame {
+        match self {
+            InputSource::Live => live,
+            InputSource::Record(replay, _) => {
+                replay.frames.push(live);
+                live
+            }
+            InputSource::Playback(replay, next) => {
+                let frame = replay.frames.get(*next).copied().unwrap_or_default();
+                *next += 1;
+                frame
+            }
+        }
+    }
+
+    pub fn is_live(&self) -> bool {
+        matches!(self, InputSource::Live)
+    }
+
+    /// The recording so far in the replay file format, None unless recording
+    pub fn recording(&self) -> Option<String> {
+        match self {
+            InputSource::Record(replay, _) => Some(replay.to_text()),
+            _ => None,
+        }
+    }
+
+    /// Saves the recording if this source is recording, after that the
+    /// source is live so the recording is only written once
+    pub fn finish(&mut self) {
+        if let InputSource::Record(replay, path) = std::mem::replace(self, InputSource::Live) {
+            if let Err(e) = replay.save(&path) {
+                eprintln!("Could not save replay to {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        simulation::Simulation,
+    };
+
+    /// Plays a run with input that changes every few frames, recording it as it goes
+    fn record_run(config: &GameConfig, replay: &mut Replay) -> (u32, u32, u64) {
+        let level = replay.level();
+        let mut sim = Simulation::new(config, replay.difficulty, replay.map_seed, replay.spawn_seed, level.as_ref());
+        for frame in 0u32.. {
+            let input = InputFrame {
+                up: frame % 90 < 30,
+                down: frame % 120 >= 100,
+                left: frame % 300 >= 280,
+                right: frame % 40 < 20,
+            };
+            replay.frames.push(input);
+            if !sim.step(input) {
+                break;
+            }
+        }
+
+        let result = sim.result();
+        (result.points, result.distance, result.frames)
+    }
+
+    #[test]
+    fn replay_round_trips_through_text() {
+        let mut replay = Replay::new(42, 7, DifficultyPreset::Hard, &GameConfig::default(), None);
+        replay.frames = (0..150).map(|i| InputFrame::from_bits(i % 16)).collect();
+
+        let parsed = Replay::parse(&replay.to_text()).unwrap();
+        assert_eq!(parsed.map_seed, 42);
+        assert_eq!(parsed.spawn_seed, 7);
+        assert_eq!(parsed.difficulty, DifficultyPreset::Hard);
+        assert_eq!(parsed.config, GameConfig::default().fingerprint());
+        assert!(parsed.level.is_none());
+        assert_eq!(parsed.frames, replay.frames);
+    }
+
+    #[test]
+    fn recorded_run_replays_to_the_same_result() {
+        let config = GameConfig::default();
+        let mut replay = Replay::new(1234, 5678, DifficultyPreset::Normal, &config, None);
+        let recorded = record_run(&config, &mut replay);
+
+        let parsed = Replay::parse(&replay.to_text()).unwrap();
+        let result = Simulation::play(&config, &parsed);
+        assert_eq!((result.points, result.distance, result.frames), recorded);
+    }
+
+    #[test]
+    fn recorded_level_run_replays_to_the_same_result() {
+        let config = GameConfig::default();
+        let level = Level::parse(include_str!("../../assets/levels/tutorial.level")).unwrap();
+        let mut replay = Replay::new(1, 2, DifficultyPreset::Normal, &config, Some(&level));
+        let recorded = record_run(&config, &mut replay);
+
+        let parsed = Replay::parse(&replay.to_text()).unwrap();
+        assert_eq!(parsed.level.as_deref(), Some(level.to_text().as_str()));
+        let result = Simulation::play(&config, &parsed);
+        assert_eq!((result.points, result.distance, result.frames), recorded);
+    }
+
+    #[test]
+    fn recording_is_written_once() {
+        let path = std::env::temp_dir().join(format!("guacamole-record-test-{}", std::process::id()));
+        let mut source = InputSource::Record(Replay::new(1, 2, DifficultyPreset::Normal, &GameConfig::default(), None), path.clone());
+        source.next_frame(InputFrame::from_bits(3));
+        assert!(source.recording().is_some());
+
+        source.finish();
+        let written = Replay::parse(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written.frames, vec![InputFrame::from_bits(3)]);
+
+        // Leaving the run after it ended doesn't write it again
+        assert!(source.is_live());
+        source.finish();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn rejects_missing_header() {
+        assert!(Replay::parse("map_seed 1\nspawn_seed 2\ndifficulty Normal\nconfig 0\nlevel 0\n").is_err());
+    }
+
+    #[test]
+    fn refuses_replays_from_a_different_config() {
+        let recorded_with = GameConfig::default();
+        let played_with = GameConfig {
+            scroll_rate: recorded_with.scroll_rate * 2.,
+            ..GameConfig::default()
+        };
+
+        let path = std::env::temp_dir().join(format!("guacamole-replay-test-{}", std::process::id()));
+        Replay::new(1, 2, DifficultyPreset::Normal, &recorded_with, None).save(&path).unwrap();
+        let same = Replay::load(&path, &recorded_with);
+        let different = Replay::load(&path, &played_with);
+        fs::remove_file(&path).unwrap();
+
+        assert!(same.is_ok());
+        assert_eq!(different.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,448 @@
+use std::{
+    fmt,
+    fs,
+    io,
+    path::PathBuf,
+};
+
+use crate::{
+    shipyard::{
+        *,
+    },
+    tetra::{
+        math::{
+            Vec2,
+        },
+    },
+    consts::{
+        *,
+    },
+    map::{
+        HexChunk,
+        HexMap,
+    },
+    components::{
+        Player,
+        Height,
+        Plane,
+        Points,
+    },
+    config::{
+        GameConfig,
+    },
+    difficulty::{
+        Difficulty,
+        DifficultyPreset,
+    },
+    level::{
+        Level,
+        LevelState,
+    },
+    scoring::{
+        Scoring,
+    },
+    replay::{
+        Replay,
+    },
+    powerups::{
+        self,
+        ActivePowerUps,
+        PowerUp,
+        PowerUpKind,
+        PowerUpSpawner,
+    },
+    systems::{
+        self,
+        Landing,
+        SpawnRng,
+        SpawnTimer,
+    },
+};
+
+use vermarine_lib::{
+    components::{
+        Transform,
+    },
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// A plane that was in the air when the run was saved
+#[derive(Serialize, Deserialize)]
+pub struct SavedPlane {
+    pub x: f64,
+    pub y: f64,
+    pub plane: Plane,
+}
+
+/// A pickup that was on the ground when the run was saved
+#[derive(Serialize, Deserialize)]
+pub struct SavedPowerUp {
+    pub x: f64,
+    pub y: f64,
+    pub kind: PowerUpKind,
+}
+
+/// A suspended run, `restore` it into a world set up by `init_gameplay` with
+/// the same seeds, difficulty and level. The rngs are saved mid stream so a
+/// resumed run carries on exactly as an uninterrupted one would
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    pub difficulty: DifficultyPreset,
+    /// `GameConfig::fingerprint` of the config the run was played with
+    pub config: u64,
+    pub map_seed: u64,
+    /// Also holds the seed `init_gameplay` is given
+    pub spawn_rng: SpawnRng,
+    /// The level being played in the level file format, None for generated maps
+    pub level: Option<String>,
+    pub level_next_spawn: usize,
+    pub level_frame: u64,
+    /// Index of the first chunk in `chunks`
+    pub first_chunk: u64,
+    /// Every loaded chunk including the tiles grown so far
+    pub chunks: Vec<HexChunk>,
+    pub map_position: (f32, f32),
+    pub player_position: (f64, f64),
+    pub player_height: f32,
+    pub planes: Vec<SavedPlane>,
+    pub spawn_timer: SpawnTimer,
+    pub scoring: Scoring,
+    pub bounced: bool,
+    pub powerups: ActivePowerUps,
+    pub powerup_spawner: PowerUpSpawner,
+    pub pickups: Vec<SavedPowerUp>,
+    /// The replay so far in the replay file format if the run is being
+    /// recorded, `Game` fills it in as the recording isn't part of the world
+    pub recording: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Serialize(ron::ser::Error),
+    Parse(ron::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "could not access save: {}", e),
+            SaveError::Serialize(e) => write!(f, "could not write save: {}", e),
+            SaveError::Parse(e) => write!(f, "could not parse save: {}", e),
+            SaveError::Invalid(msg) => write!(f, "invalid save: {}", msg),
+        }
+    }
+}
+
+impl SaveGame {
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("guacamole-runner").join("savegame.ron"))
+    }
+
+    /// Takes a snapshot of a run that is still going
+    pub fn capture(world: &World) -> Self {
+        let (difficulty, map_seed, first_chunk, chunks, map_position) = world.run(|difficulty: UniqueView<Difficulty>, map: UniqueView<HexMap>| {
+            let (first_chunk, chunks) = map.loaded_chunks();
+            (difficulty.preset, map.seed, first_chunk, chunks.cloned().collect::<Vec<_>>(), (map.position.x, map.position.y))
+        });
+
+        let config = world.run(|config: UniqueView<GameConfig>| config.fingerprint());
+
+        let (level, level_next_spawn, level_frame) = world.run(|state: UniqueView<LevelState>| {
+            (state.level.as_ref().map(Level::to_text), state.next_spawn, state.frame)
+        });
+
+        let (player_position, player_height) = world.run(|players: View<Player>, transforms: View<Transform>, heights: View<Height>| {
+            let (_, transform, height) = (&players, &transforms, &heights).iter().next().unwrap();
+            ((transform.x, transform.y), height.0)
+        });
+
+        let planes = world.run(|planes: View<Plane>, transforms: View<Transform>| {
+            (&planes, &transforms)
+                .iter()
+                .map(|(plane, transform)| SavedPlane {
+                    x: transform.x,
+                    y: transform.y,
+                    plane: *plane,
+                })
+                .collect()
+        });
+
+        let pickups = world.run(|pickups: View<PowerUp>, transforms: View<Transform>| {
+            (&pickups, &transforms)
+                .iter()
+                .map(|(pickup, transform)| SavedPowerUp {
+                    x: transform.x,
+                    y: transform.y,
+                    kind: pickup.0,
+                })
+                .collect()
+        });
+
+        let (spawn_rng, spawn_timer, powerup_spawner) = world.run(|spawn_rng: UniqueView<SpawnRng>, spawn_timer: UniqueView<SpawnTimer>, powerup_spawner: UniqueView<PowerUpSpawner>| {
+            (spawn_rng.clone(), spawn_timer.clone(), powerup_spawner.clone())
+        });
+
+        let (scoring, bounced, powerups) = world.run(|scoring: UniqueView<Scoring>, landing: UniqueView<Landing>, powerups: UniqueView<ActivePowerUps>| {
+            (scoring.clone(), landing.bounced, powerups.clone())
+        });
+
+        Self {
+            difficulty,
+            config,
+            map_seed,
+            spawn_rng,
+            level,
+            level_next_spawn,
+            level_frame,
+            first_chunk,
+            chunks,
+            map_position,
+            player_position,
+            player_height,
+            planes,
+            spawn_timer,
+            scoring,
+            bounced,
+            powerups,
+            powerup_spawner,
+            pickups,
+            recording: None,
+        }
+    }
+
+    /// The level the run was on, `load` already checked it parses
+    pub fn level(&self) -> Option<Level> {
+        self.level.as_deref().and_then(|text| Level::parse(text).ok())
+    }
+
+    /// Saves only play out the same under the config they were made with
+    pub fn check_config(&self, config: &GameConfig) -> Result<(), SaveError> {
+        if self.config != config.fingerprint() {
+            return Err(SaveError::Invalid(String::from("saved with a different game config")));
+        }
+        Ok(())
+    }
+
+    /// Puts the saved state into a freshly initialised world
+    pub fn restore(self, world: &World) {
+        let SaveGame {
+            level_next_spawn,
+            level_frame,
+            first_chunk,
+            chunks,
+            map_position,
+            player_position,
+            player_height,
+            planes,
+            spawn_rng,
+            spawn_timer,
+            scoring,
+            bounced,
+            powerups,
+            powerup_spawner,
+            pickups,
+            ..
+        } = self;
+
+        world.run(|mut map: UniqueViewMut<HexMap>, mut state: UniqueViewMut<LevelState>| {
+            map.restore_chunks(first_chunk, chunks, Vec2::new(map_position.0, map_position.1));
+            state.next_spawn = level_next_spawn;
+            state.frame = level_frame;
+        });
+
+        world.run(|players: View<Player>, mut transforms: ViewMut<Transform>, mut heights: ViewMut<Height>| {
+            for (_, transform, height) in (&players, &mut transforms, &mut heights).iter() {
+                transform.x = player_position.0;
+                transform.y = player_position.1;
+                height.0 = player_height;
+            }
+        });
+
+        world.run(|all_storages: AllStoragesViewMut| {
+            for saved in &planes {
+                systems::spawn_plane(&all_storages, saved.x, saved.y, saved.plane);
+            }
+            for saved in &pickups {
+                powerups::spawn_powerup(&all_storages, saved.x, saved.y, saved.kind);
+            }
+        });
+
+        world.run(|mut rng: UniqueViewMut<SpawnRng>, mut timer: UniqueViewMut<SpawnTimer>, mut spawner: UniqueViewMut<PowerUpSpawner>| {
+            *rng = spawn_rng;
+            *timer = spawn_timer;
+            *spawner = powerup_spawner;
+        });
+
+        world.run(|mut current: UniqueViewMut<Scoring>, mut points: UniqueViewMut<Points>, mut landing: UniqueViewMut<Landing>, mut active: UniqueViewMut<ActivePowerUps>| {
+            points.0 = scoring.breakdown.total();
+            *current = scoring;
+            landing.bounced = bounced;
+            *active = powerups;
+        });
+
+        // The player's collider scales with its sprite, which is only sized at the end of a frame
+        world.run(systems::player_height_visualiser);
+    }
+
+    pub fn to_text(&self) -> Result<String, SaveError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(SaveError::Serialize)
+    }
+
+    pub fn save(&self) -> Result<(), SaveError> {
+        let path = Self::path().ok_or_else(|| SaveError::Invalid(String::from("no data directory to save to")))?;
+        let contents = self.to_text()?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(SaveError::Io)?;
+        }
+        fs::write(&path, contents).map_err(SaveError::Io)
+    }
+
+    /// Whether there is a suspended run to continue
+    pub fn exists() -> bool {
+        Self::path().map_or(false, |path| path.is_file())
+    }
+
+    pub fn load() -> Result<Self, SaveError> {
+        let path = Self::path().ok_or_else(|| SaveError::Invalid(String::from("no data directory to load from")))?;
+        let contents = fs::read_to_string(&path).map_err(SaveError::Io)?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, SaveError> {
+        let save: SaveGame = ron::de::from_str(contents).map_err(SaveError::Parse)?;
+        save.validate()?;
+        Ok(save)
+    }
+
+    fn validate(&self) -> Result<(), SaveError> {
+        if self.chunks.is_empty() {
+            return Err(SaveError::Invalid(String::from("no map chunks")));
+        }
+        for (i, chunk) in self.chunks.iter().enumerate() {
+            if chunk.tiles.len() != CHUNK_WIDTH * HEIGHT {
+                return Err(SaveError::Invalid(format!(
+                    "chunk {} has {} tiles, expected {}",
+                    self.first_chunk + i as u64, chunk.tiles.len(), CHUNK_WIDTH * HEIGHT
+                )));
+            }
+        }
+        if let Some(text) = &self.level {
+            Level::parse(text).map_err(|e| SaveError::Invalid(format!("saved level: {}", e)))?;
+        }
+        if let Some(text) = &self.recording {
+            Replay::parse(text).map_err(|e| SaveError::Invalid(format!("saved recording: {}", e)))?;
+        }
+        Ok(())
+    }
+
+    /// Removes the save so a suspended run can only be carried on once
+    pub fn delete() {
+        if let Some(path) = Self::path() {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    eprintln!("Could not delete saved run {}: {}", path.display(), e);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        components::{
+            Textures,
+        },
+        input::{
+            InputFrame,
+        },
+        simulation,
+    };
+
+    fn input(frame: u32) -> InputFrame {
+        InputFrame {
+            up: frame % 90 < 30,
+            down: frame % 120 >= 100,
+            left: frame % 300 >= 280,
+            right: frame % 40 < 20,
+        }
+    }
+
+    #[test]
+    fn refuses_a_different_config() {
+        let config = GameConfig::default();
+        let save = SaveGame::capture(&new_world(&config));
+        let changed = GameConfig {
+            scroll_rate: config.scroll_rate * 2.,
+            ..GameConfig::default()
+        };
+
+        assert!(save.check_config(&config).is_ok());
+        assert!(matches!(save.check_config(&changed), Err(SaveError::Invalid(_))));
+    }
+
+    fn new_world(config: &GameConfig) -> World {
+        let world = World::new();
+        simulation::init_gameplay(&world, config, DifficultyPreset::Normal, 1234, 5678, Textures::default(), None);
+        world
+    }
+
+    fn step(world: &World, frame: u32) {
+        world.run(|mut current: UniqueViewMut<InputFrame>| {
+            *current = input(frame);
+        });
+        simulation::run_gameplay(world);
+    }
+
+    /// Points, map distance, player position and how many planes are up
+    fn snapshot(world: &World) -> (u32, u32, (f64, f64), usize) {
+        world.run(|points: UniqueView<Points>, map: UniqueView<HexMap>, players: View<Player>, transforms: View<Transform>, planes: View<Plane>| {
+            let (_, transform) = (&players, &transforms).iter().next().unwrap();
+            (points.0, map.distance(), (transform.x, transform.y), planes.iter().count())
+        })
+    }
+
+    #[test]
+    fn restored_run_carries_on_like_the_original() {
+        let config = GameConfig::default();
+        let original = new_world(&config);
+        for frame in 0..120 {
+            step(&original, frame);
+        }
+        assert!(!simulation::run_is_over(&original));
+
+        let text = SaveGame::capture(&original).to_text().unwrap();
+        let resumed = new_world(&config);
+        SaveGame::parse(&text).unwrap().restore(&resumed);
+        assert_eq!(snapshot(&resumed), snapshot(&original));
+
+        for frame in 120..600 {
+            if simulation::run_is_over(&original) {
+                break;
+            }
+            step(&original, frame);
+            step(&resumed, frame);
+            assert_eq!(snapshot(&resumed), snapshot(&original), "frame {}", frame);
+        }
+        assert_eq!(simulation::outcome(&resumed).is_some(), simulation::outcome(&original).is_some());
+    }
+
+    #[test]
+    fn rejects_saves_with_short_chunks() {
+        let config = GameConfig::default();
+        let mut save = SaveGame::capture(&new_world(&config));
+        save.chunks[0].tiles.pop();
+
+        let text = save.to_text().unwrap();
+        assert!(matches!(SaveGame::parse(&text), Err(SaveError::Invalid(_))));
+    }
+}
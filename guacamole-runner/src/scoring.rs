@@ -0,0 +1,176 @@
+use crate::{
+    shipyard::{
+        *,
+    },
+    consts::{
+        *,
+    },
+    map::{
+        HexMap,
+    },
+    hex::{
+        Axial,
+    },
+    components::{
+        Player,
+        Height,
+        Points,
+    },
+    config::{
+        GameConfig,
+    },
+    powerups::{
+        ActivePowerUps,
+        PowerUpKind,
+    },
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Where the final score came from, shown on the death screen
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ScoreBreakdown {
+    /// Flat points for every grown tile
+    pub grown: u32,
+    /// Extra points for growing tiles on consecutive frames
+    pub combo: u32,
+    /// Extra points from the plane jump chain multiplier
+    pub chain: u32,
+    /// Bonuses for growing every tile of a tilled strip
+    pub strips: u32,
+    /// Points doubled by the score doubler power-up
+    pub doubler: u32,
+    pub best_combo: u32,
+    pub best_chain: u32,
+    pub strips_cleared: u32,
+}
+
+impl ScoreBreakdown {
+    pub fn total(&self) -> u32 {
+        self.grown + self.combo + self.chain + self.strips + self.doubler
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+"   Grown tiles        {:>6}
+   Combo bonus        {:>6}  (best combo {} frames)
+   Jump chain bonus   {:>6}  (best chain {} planes)
+   Strip bonus        {:>6}  ({} strips cleared)
+   Score doubler      {:>6}
+",
+            self.grown,
+            self.combo, self.best_combo,
+            self.chain, self.best_chain,
+            self.strips, self.strips_cleared,
+            self.doubler,
+        )
+    }
+}
+
+/// Only the state carried between frames is saved, the rest is used up by `score_frame`
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Scoring {
+    /// Tiles grown this frame, filled in by the systems that grow tiles
+    #[serde(skip)]
+    pub grown_this_frame: Vec<Axial>,
+    /// Set by `player_platform_check` when the player is on a plane this frame
+    #[serde(skip)]
+    pub touching_plane: bool,
+    was_touching_plane: bool,
+    /// Consecutive frames with at least one tile grown
+    pub combo: u32,
+    /// Planes jumped off in a row without dropping below `CHAIN_HEIGHT`
+    pub jump_chain: u32,
+    pub breakdown: ScoreBreakdown,
+}
+
+impl Scoring {
+    /// Multiplier on grown tile points, starts at 1 and goes up a step every `COMBO_STEP_FRAMES`
+    pub fn combo_multiplier(&self) -> u32 {
+        (1 + self.combo / COMBO_STEP_FRAMES).min(MAX_COMBO_MULTIPLIER)
+    }
+
+    pub fn chain_multiplier(&self) -> f32 {
+        (1. + self.jump_chain as f32 * CHAIN_STEP).min(MAX_CHAIN_MULTIPLIER)
+    }
+}
+
+/// Returns the first hex of the tilled strip `hex` is in and its length if every tile in it is grown.
+/// Strips running into a chunk that isn't loaded can't be checked so they don't count as complete
+fn completed_strip(map: &HexMap, hex: Axial) -> Option<(Axial, u32)> {
+    let step = Axial::new(1, 0);
+    let mut start = hex;
+    loop {
+        let tile = map.get(start - step)?;
+        if !tile.is_tilled {
+            break;
+        }
+        start = start - step;
+    }
+
+    let mut len = 0;
+    let mut next = start;
+    loop {
+        let tile = map.get(next)?;
+        if !tile.is_tilled {
+            break;
+        }
+        if !tile.is_grown {
+            return None;
+        }
+        len += 1;
+        next = next + step;
+    }
+    Some((start, len))
+}
+
+/// Turns this frame's grown tiles and plane jumps into points
+pub fn score_frame(config: UniqueView<GameConfig>, powerups: UniqueView<ActivePowerUps>, player: View<Player>, heights: View<Height>, map: UniqueView<HexMap>, mut points: UniqueViewMut<Points>, mut scoring: UniqueViewMut<Scoring>) {
+    let scoring = &mut *scoring;
+    let (_, height) = (&player, &heights).iter().next().unwrap();
+
+    if scoring.touching_plane && !scoring.was_touching_plane {
+        scoring.jump_chain += 1;
+        scoring.breakdown.best_chain = scoring.breakdown.best_chain.max(scoring.jump_chain);
+    }
+    if height.0 < config.start_height * CHAIN_HEIGHT {
+        scoring.jump_chain = 0;
+    }
+    scoring.was_touching_plane = scoring.touching_plane;
+    scoring.touching_plane = false;
+
+    let before = scoring.breakdown.total();
+    let grown = scoring.grown_this_frame.len() as u32;
+    if grown == 0 {
+        scoring.combo = 0;
+    } else {
+        scoring.combo += 1;
+        scoring.breakdown.best_combo = scoring.breakdown.best_combo.max(scoring.combo);
+
+        let base = grown * config.points_grow;
+        scoring.breakdown.grown += base;
+        scoring.breakdown.combo += base * (scoring.combo_multiplier() - 1);
+        scoring.breakdown.chain += (base as f32 * (scoring.chain_multiplier() - 1.)) as u32;
+    }
+
+    let mut cleared: Vec<Axial> = Vec::new();
+    for &hex in &scoring.grown_this_frame {
+        if let Some((start, len)) = completed_strip(&map, hex) {
+            if !cleared.contains(&start) {
+                cleared.push(start);
+                scoring.breakdown.strips += len * STRIP_BONUS;
+                scoring.breakdown.strips_cleared += 1;
+            }
+        }
+    }
+    scoring.grown_this_frame.clear();
+
+    if powerups.is_active(PowerUpKind::ScoreDoubler) {
+        scoring.breakdown.doubler += scoring.breakdown.total() - before;
+    }
+
+    points.0 = scoring.breakdown.total();
+}
@@ -0,0 +1,287 @@
+use crate::{
+    shipyard::{
+        *,
+    },
+    tetra::{
+        math::{
+            Vec2,
+        },
+    },
+    consts::{
+        *,
+    },
+    map::{
+        HexMap,
+    },
+    components::{
+        Player,
+        Height,
+        Points,
+        Textures,
+        RunOutcome,
+    },
+    input::{
+        InputFrame,
+    },
+    replay::{
+        Replay,
+    },
+    config::{
+        GameConfig,
+    },
+    difficulty::{
+        self,
+        Difficulty,
+        DifficultyPreset,
+    },
+    collision::{
+        Collider,
+    },
+    spatial::{
+        self,
+        SpatialHash,
+    },
+    scoring::{
+        self,
+        Scoring,
+    },
+    powerups::{
+        self,
+        ActivePowerUps,
+        PowerUpSpawner,
+    },
+    level::{
+        self,
+        Level,
+        LevelState,
+    },
+    systems,
+};
+
+use vermarine_lib::{
+    components::{
+        Transform,
+    },
+    rendering::{
+        Sprite,
+        draw_buffer::{
+            DrawCommand,
+        },
+    },
+};
+
+use rand::{
+    Rng,
+    SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+
+/// Stops headless runs where the player manages to stay up forever
+const MAX_FRAMES: u64 = 60 * 60 * 30;
+
+/// Adds the uniques and the player that every run needs, shared by `Game` and `Simulation`.
+/// Runs on a `level` use its map and planes instead of generated ones
+pub fn init_gameplay(world: &World, config: &GameConfig, preset: DifficultyPreset, map_seed: u64, spawn_seed: u64, textures: Textures, level: Option<&Level>) {
+    match level {
+        Some(level) => {
+            world.add_unique(HexMap::with_generator(HEIGHT, map_seed, config.max_floor_height, Box::new(level.generator())));
+            world.add_unique(LevelState::new(level));
+        }
+        None => {
+            world.add_unique(HexMap::with_seed(HEIGHT, map_seed, config.max_floor_height));
+            world.add_unique(LevelState::default());
+        }
+    }
+    world.add_unique(InputFrame::default());
+    world.add_unique(systems::SpawnTimer::new(config.spawn_interval));
+    world.add_unique(systems::SpawnRng::new(spawn_seed));
+    world.add_unique(systems::PlaneStats::default());
+    world.add_unique(Difficulty::new(preset));
+    world.add_unique(SpatialHash::default());
+    world.add_unique(systems::Landing::default());
+    world.add_unique(Points::new());
+    world.add_unique(Scoring::default());
+    world.add_unique(ActivePowerUps::default());
+    world.add_unique(PowerUpSpawner::new(spawn_seed));
+    world.add_unique(textures);
+
+    world
+        .entity_builder()
+        .with(Sprite::from_command(
+            DrawCommand::new(textures.player)
+            .scale(Vec2::new(3., 3.))
+            .draw_layer(draw_layers::PLAYER)
+            .origin(Vec2::new(20., 18.))
+        ))
+        .with(Transform::new(200., 360.))
+        .with(Player {})
+        .with(Collider::rect(-20., -8., 36., 16.))
+        .with(Height(config.start_height))
+        .build();
+
+    world.add_unique(config.clone());
+}
+
+/// Advances every gameplay system by one frame using whatever `InputFrame` is in the world
+pub fn run_gameplay(world: &World) {
+    world.run(difficulty::ramp_difficulty);
+    world.run(systems::scroll_map);
+    world.run(systems::stream_map);
+    world.run(systems::move_player);
+    if world.run(|level: UniqueView<LevelState>| level.is_scripted()) {
+        world.run(level::scripted_spawner);
+    } else {
+        world.run(systems::platform_spawner);
+    }
+    world.run(powerups::powerup_spawner);
+    world.run(systems::move_planes);
+    world.run(powerups::magnet_planes);
+    world.run(powerups::move_powerups);
+    world.run(systems::cull_planes);
+    world.run(powerups::cull_powerups);
+    world.run(systems::grow_ground);
+    world.run(spatial::rebuild_spatial_hash);
+    world.run(powerups::collect_powerups);
+    world.run(systems::player_platform_check);
+    world.run(systems::player_landing);
+    world.run(level::level_finish);
+    world.run(scoring::score_frame);
+    world.run(powerups::tick_powerups);
+    // Not just visual, the player's collider scales with its sprite
+    world.run(systems::player_height_visualiser);
+}
+
+/// Swaps in a new config for a run that is already going. `max_floor_height`
+/// only takes effect from the next `init_gameplay`, changing it mid run would
+/// make chunks generated from then on differ from the same seed's other runs
+pub fn apply_config(world: &World, config: &GameConfig) {
+    world.run(|mut current: UniqueViewMut<GameConfig>| {
+        *current = config.clone();
+    });
+}
+
+/// How the run ended, None while the player is still going
+pub fn outcome(world: &World) -> Option<RunOutcome> {
+    world.run(|landing: UniqueView<systems::Landing>| {
+        match landing.death {
+            Some(cause) => Some(RunOutcome::Died(cause)),
+            None if landing.finished => Some(RunOutcome::Finished),
+            None => None,
+        }
+    })
+}
+
+pub fn run_is_over(world: &World) -> bool {
+    outcome(world).is_some()
+}
+
+pub struct RunResult {
+    pub points: u32,
+    pub distance: u32,
+    pub frames: u64,
+}
+
+/// Runs the game systems without a window so runs can be simulated on machines
+/// without a GPU or display
+pub struct Simulation {
+    world: World,
+    frames: u64,
+}
+
+impl Simulation {
+    pub fn new(config: &GameConfig, preset: DifficultyPreset, map_seed: u64, spawn_seed: u64, level: Option<&Level>) -> Self {
+        let world = World::new();
+        init_gameplay(&world, config, preset, map_seed, spawn_seed, Textures::default(), level);
+
+        Self {
+            world,
+            frames: 0,
+        }
+    }
+
+    /// Feeds every frame of `replay` through the systems, the input after the
+    /// last recorded frame is empty
+    pub fn play(config: &GameConfig, replay: &Replay) -> RunResult {
+        let level = replay.level();
+        let mut sim = Self::new(config, replay.difficulty, replay.map_seed, replay.spawn_seed, level.as_ref());
+        let mut frames = replay.frames.iter().copied();
+        while sim.step(frames.next().unwrap_or_default()) {}
+        sim.result()
+    }
+
+    /// Steps the world by one frame, returns false once the run is over
+    pub fn step(&mut self, input: InputFrame) -> bool {
+        if self.is_over() {
+            return false;
+        }
+
+        self.world.run(|mut frame: UniqueViewMut<InputFrame>| {
+            *frame = input;
+        });
+        run_gameplay(&self.world);
+        self.frames += 1;
+
+        !self.is_over()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.frames >= MAX_FRAMES || run_is_over(&self.world)
+    }
+
+    pub fn result(&self) -> RunResult {
+        self.world.run(|points: UniqueView<Points>, map: UniqueView<HexMap>| {
+            RunResult {
+                points: points.0,
+                distance: map.distance(),
+                frames: self.frames,
+            }
+        })
+    }
+}
+
+/// Runs `runs` headless simulations with random input and prints how each one went
+pub fn run_headless(config: &GameConfig, preset: DifficultyPreset, runs: u32, seed: Option<u64>) {
+    let mut total_points = 0u64;
+    let mut total_distance = 0u64;
+
+    for run in 0..runs {
+        let seed = seed.map(|seed| seed.wrapping_add(run as u64)).unwrap_or_else(rand::random);
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let mut sim = Simulation::new(config, preset, seed, rng.gen(), None);
+
+        // Hold a random combination of keys for a random number of frames
+        let mut input = InputFrame::default();
+        let mut hold = 0;
+        loop {
+            if hold == 0 {
+                input = InputFrame {
+                    up: rng.gen_bool(0.3),
+                    down: rng.gen_bool(0.3),
+                    left: rng.gen_bool(0.1),
+                    right: rng.gen_bool(0.3),
+                };
+                hold = rng.gen_range(5, 30);
+            }
+            hold -= 1;
+
+            if !sim.step(input) {
+                break;
+            }
+        }
+
+        let result = sim.result();
+        println!(
+            "run {} seed {}: {} points, distance {}, {} frames",
+            run, seed, result.points, result.distance, result.frames
+        );
+        total_points += result.points as u64;
+        total_distance += result.distance as u64;
+    }
+
+    if runs > 0 {
+        println!(
+            "average over {} runs: {} points, distance {}",
+            runs, total_points / runs as u64, total_distance / runs as u64
+        );
+    }
+}
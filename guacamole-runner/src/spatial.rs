@@ -0,0 +1,126 @@
+use std::{
+    collections::HashMap,
+};
+
+use crate::{
+    shipyard::{
+        *,
+    },
+    tetra::{
+        math::{
+            Vec2,
+        },
+    },
+    collision::{
+        Aabb,
+        Collider,
+        WorldShape,
+    },
+};
+
+use vermarine_lib::{
+    components::{
+        Transform,
+    },
+    rendering::{
+        Sprite,
+    },
+};
+
+/// Roughly the size of a plane, most colliders only land in a few cells
+pub const CELL_SIZE: f32 = 128.;
+
+struct Entry {
+    id: EntityId,
+    shape: WorldShape,
+    bounds: Aabb,
+}
+
+/// Uniform grid of every collider's world shape, rebuilt each frame so
+/// collision queries only test colliders in nearby cells
+pub struct SpatialHash {
+    cell_size: f32,
+    entries: Vec<Entry>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            entries: Vec::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        // Keep the cell vecs around so rebuilding doesn't reallocate every frame
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+    }
+
+    pub fn insert(&mut self, id: EntityId, shape: WorldShape) {
+        let bounds = shape.bounds();
+        let index = self.entries.len();
+        self.entries.push(Entry {
+            id,
+            shape,
+            bounds,
+        });
+
+        let (min, max) = self.cell_range(&bounds);
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                self.cells.entry((x, y)).or_default().push(index);
+            }
+        }
+    }
+
+    /// Every entity whose collider intersects `shape`
+    pub fn query(&self, shape: &WorldShape) -> Vec<EntityId> {
+        let bounds = shape.bounds();
+        let (min, max) = self.cell_range(&bounds);
+
+        let mut found: Vec<usize> = Vec::new();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                if let Some(cell) = self.cells.get(&(x, y)) {
+                    found.extend(cell.iter().copied());
+                }
+            }
+        }
+        // Colliders spanning several cells show up once per cell
+        found.sort_unstable();
+        found.dedup();
+
+        found
+            .into_iter()
+            .map(|index| &self.entries[index])
+            .filter(|entry| entry.bounds.intersects(&bounds) && entry.shape.intersects(shape))
+            .map(|entry| entry.id)
+            .collect()
+    }
+
+    fn cell_range(&self, bounds: &Aabb) -> (Vec2<i32>, Vec2<i32>) {
+        let cell = |value: f32| (value / self.cell_size).floor() as i32;
+        (
+            Vec2::new(cell(bounds.min.x), cell(bounds.min.y)),
+            Vec2::new(cell(bounds.max.x), cell(bounds.max.y)),
+        )
+    }
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(CELL_SIZE)
+    }
+}
+
+pub fn rebuild_spatial_hash(transforms: View<Transform>, colliders: View<Collider>, sprites: View<Sprite>, mut hash: UniqueViewMut<SpatialHash>) {
+    hash.clear();
+    for (id, (transform, collider, sprite)) in (&transforms, &colliders, &sprites).iter().with_id() {
+        hash.insert(id, collider.world_shape(transform, Some(sprite)));
+    }
+}
@@ -3,11 +3,6 @@ use crate::{
         *,
     },
     tetra::{
-        InputContext,
-        input::{
-            self,
-            Key,
-        },
         math::{
             Vec2,
         },
@@ -18,13 +13,40 @@ use crate::{
     map::{
         HexMap,
     },
+    hex::{
+        Axial,
+        Cube,
+    },
     components::{
         Player,
         Plane,
         Direction,
-        Collider,
+        FlightPattern,
         Height,
-        Points,
+        DeathCause,
+        Textures,
+    },
+    input::{
+        InputFrame,
+    },
+    config::{
+        GameConfig,
+    },
+    difficulty::{
+        Difficulty,
+    },
+    collision::{
+        Collider,
+    },
+    spatial::{
+        SpatialHash,
+    },
+    scoring::{
+        Scoring,
+    },
+    powerups::{
+        ActivePowerUps,
+        PowerUpKind,
     },
 };
 
@@ -34,36 +56,46 @@ use vermarine_lib::{
     },
     rendering::{
         Sprite,
-        Drawables,
         draw_buffer::{
             DrawCommand,
         },
     },
 };
 
-pub fn move_player(ctx: UniqueView<InputContext>, players: View<Player>, mut transforms: ViewMut<Transform>) {
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use rand::{
+    Rng,
+    SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+
+pub fn move_player(config: UniqueView<GameConfig>, input: UniqueView<InputFrame>, players: View<Player>, mut transforms: ViewMut<Transform>) {
     let mut movement: Vec2<f32> = Vec2::zero();
 
-    if input::is_key_down(&ctx, Key::Down) {
+    if input.down {
         movement += Vec2::new(-0.5, 2.);
     }
-    if input::is_key_down(&ctx, Key::Up) {
+    if input.up {
         movement += Vec2::new(-0.5, -2.);
     }
 
     if movement == Vec2::zero() {
-        if input::is_key_down(&ctx, Key::Left) {
+        if input.left {
             movement += Vec2::new(-5., 0.);
         }
-        else if input::is_key_down(&ctx, Key::Right) {
+        else if input.right {
             movement += Vec2::new(1., 0.);
         }
-    } else if input::is_key_down(&ctx, Key::Left) {
+    } else if input.left {
         movement = Vec2::new(-5., 0.);
     }
 
     if movement != Vec2::new(0.0, 0.0) {
-        movement *= PLAYER_SPEED;
+        movement *= config.player_speed;
         movement.x = movement.x.floor();
         movement.y = movement.y.floor();        
     }
@@ -77,10 +109,15 @@ pub fn move_player(ctx: UniqueView<InputContext>, players: View<Player>, mut tra
     }
 }
 
-pub fn scroll_map(mut map: UniqueViewMut<HexMap>) {
-    map.position.x -= SCROLL_RATE;
+pub fn scroll_map(config: UniqueView<GameConfig>, difficulty: UniqueView<Difficulty>, mut map: UniqueViewMut<HexMap>) {
+    map.position.x -= difficulty.scroll_rate(&config);
+}
+
+pub fn stream_map(mut map: UniqueViewMut<HexMap>) {
+    map.stream_chunks();
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SpawnTimer {
     cur: i32,
     max: i32,
@@ -93,68 +130,188 @@ impl SpawnTimer {
             max,
         }
     }
-}
 
-pub fn platform_spawner(all_storages: AllStoragesViewMut) {
-    let spawn = all_storages.run(|mut spawn_timer: UniqueViewMut<SpawnTimer>| {
-        if spawn_timer.cur <= 0 {
-            spawn_timer.cur = spawn_timer.max;
-            true    
+    /// Changes the interval, the current countdown is kept if it is shorter
+    pub fn set_max(&mut self, max: i32) {
+        self.max = max;
+        self.cur = self.cur.min(max);
+    }
+
+    /// Counts down one frame, returns true and restarts when it runs out
+    pub fn tick(&mut self) -> bool {
+        if self.cur <= 0 {
+            self.cur = self.max;
+            true
         } else {
-            spawn_timer.cur -= 1;
+            self.cur -= 1;
             false
         }
+    }
+}
+
+/// Rng owned by the world so a run's plane spawns can be reproduced from its seed
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SpawnRng {
+    pub seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl SpawnRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PlaneKind {
+    Diagonal,
+    Cruiser,
+    SineWave,
+    Banking,
+    Heavy,
+}
+
+/// Relative chance of `platform_spawner` picking each kind of plane
+const PLANE_WEIGHTS: [(PlaneKind, u32); 5] = [
+    (PlaneKind::Diagonal, 50),
+    (PlaneKind::Cruiser, 15),
+    (PlaneKind::SineWave, 15),
+    (PlaneKind::Banking, 10),
+    (PlaneKind::Heavy, 10),
+];
+
+fn pick_plane_kind(rng: &mut ChaCha8Rng) -> PlaneKind {
+    let total: u32 = PLANE_WEIGHTS.iter().map(|(_, weight)| weight).sum();
+    let mut roll = rng.gen_range(0, total);
+    for &(kind, weight) in PLANE_WEIGHTS.iter() {
+        if roll < weight {
+            return kind;
+        }
+        roll -= weight;
+    }
+    PlaneKind::Diagonal
+}
+
+/// Sprite rotation for a plane flying up, down or straight left
+fn plane_rotation(heading: Option<Direction>) -> f32 {
+    match heading {
+        Some(Direction::Up) => 0.,
+        Some(Direction::Down) => std::f32::consts::PI,
+        None => -std::f32::consts::FRAC_PI_2,
+    }
+}
+
+/// Hitbox of the plane sprite facing up, it gets rotated and scaled along with the sprite
+fn plane_collider() -> Collider {
+    Collider::rect(-32., -16., 64., 26.)
+}
+
+pub fn platform_spawner(all_storages: AllStoragesViewMut) {
+    let spawn = all_storages.run(|mut spawn_timer: UniqueViewMut<SpawnTimer>| {
+        spawn_timer.tick()
     });
 
     if spawn {
-        use rand::prelude::*;
-
-        let mut rng = rand::thread_rng();
-        let (x, mut y) = (rng.gen_range(800, 1280), rng.gen_range(0, 2) * 720);
-        let direction;
-        let rotation;
-        let collider;
-        if y == 0 {
-            y = -36;
-            direction = Direction::Down;
-            rotation = std::f32::consts::PI;
-            collider = Collider::new(-32 * 2, -10 * 2, 64 * 2, 26 * 2);
-        } else {
-            direction = Direction::Up;
-            rotation = 0.;
-            y += 36;
-            collider = Collider::new(-32 * 2, -16 * 2, 64 * 2, 26 * 2);
-        }
+        let (x, y, pattern) = all_storages.run(|mut spawn_rng: UniqueViewMut<SpawnRng>| {
+            let rng = &mut spawn_rng.rng;
+            let kind = pick_plane_kind(rng);
+
+            // Planes flying across come in from the right, the rest from the top or bottom
+            if kind == PlaneKind::Cruiser || kind == PlaneKind::SineWave {
+                let y = rng.gen_range(100, WINDOW_HEIGHT - 100);
+                let pattern = if kind == PlaneKind::Cruiser {
+                    FlightPattern::Cruiser
+                } else {
+                    FlightPattern::SineWave {
+                        base_y: y as f64,
+                        amplitude: rng.gen_range(40., 100.),
+                        period: rng.gen_range(60., 120.),
+                    }
+                };
+                return (WINDOW_WIDTH + 72, y, pattern);
+            }
 
-        let tex = all_storages.run(|drawables: NonSendSync<UniqueView<Drawables>>| {
-            drawables.alias[textures::AEROPLANE]
+            let x = rng.gen_range(800, 1280);
+            let (y, direction) = if rng.gen_range(0, 2) == 0 {
+                (-36, Direction::Down)
+            } else {
+                (WINDOW_HEIGHT + 36, Direction::Up)
+            };
+            let pattern = match kind {
+                PlaneKind::Banking => FlightPattern::Banking {
+                    direction,
+                    turn_y: rng.gen_range(200., WINDOW_HEIGHT as f64 - 200.),
+                    turned: false,
+                },
+                PlaneKind::Heavy => FlightPattern::Heavy(direction),
+                _ => FlightPattern::Diagonal(direction),
+            };
+            (x, y, pattern)
         });
 
-        all_storages
-            .entity_builder()
-            .with(Transform::new(x as f64, y as f64))
-            .with(Sprite::from_command(
-                DrawCommand::new(tex)
-                .scale(Vec2::new(2., 2.))
-                .draw_layer(draw_layers::PLANE)
-                .rotation(rotation)
-                .origin(Vec2::new(36., 36.))
-            ))
-            .with(Plane::new(direction))
-            .with(collider)
-            .build();
+        spawn_plane(&all_storages, x as f64, y as f64, Plane::new(pattern));
     }
 }
 
-pub fn move_planes(mut transforms: ViewMut<Transform>, planes: View<Plane>) {
-    for (transform, plane) in (&mut transforms, &planes).iter() {
+/// Creates the entity for `plane` at `x, y`, used by the spawners and when resuming a saved run
+pub fn spawn_plane(all_storages: &AllStorages, x: f64, y: f64, plane: Plane) {
+    let heading = plane.heading();
+    let scale = if let FlightPattern::Heavy(_) = plane.pattern { 3 } else { 2 };
+
+    let tex = all_storages.run(|textures: UniqueView<Textures>| {
+        textures.aeroplane
+    });
+
+    all_storages
+        .entity_builder()
+        .with(Transform::new(x, y))
+        .with(Sprite::from_command(
+            DrawCommand::new(tex)
+            .scale(Vec2::new(scale as f32, scale as f32))
+            .draw_layer(draw_layers::PLANE)
+            .rotation(plane_rotation(heading))
+            .origin(Vec2::new(36., 36.))
+        ))
+        .with(plane)
+        .with(plane_collider())
+        .build();
+}
+
+pub fn move_planes(config: UniqueView<GameConfig>, difficulty: UniqueView<Difficulty>, mut transforms: ViewMut<Transform>, mut planes: ViewMut<Plane>, mut sprites: ViewMut<Sprite>) {
+    let scroll_rate = difficulty.scroll_rate(&config) as f64;
+    for (transform, plane, sprite) in (&mut transforms, &mut planes, &mut sprites).iter() {
+        plane.age += 1;
+
         let movement;
-        match plane.direction {
-            Direction::Up => {
-                movement = Vec2::new(-SCROLL_RATE as f64 * 2., -4.)
+        match &mut plane.pattern {
+            FlightPattern::Diagonal(direction) => {
+                movement = Vec2::new(-scroll_rate * 2., 4. * direction.sign());
+            }
+            FlightPattern::Cruiser => {
+                movement = Vec2::new(-scroll_rate * 2.5, 0.);
+            }
+            FlightPattern::SineWave { base_y, amplitude, period } => {
+                let phase = plane.age as f64 / *period * std::f64::consts::PI * 2.;
+                let y = *base_y + *amplitude * phase.sin();
+                movement = Vec2::new(-scroll_rate * 2., y - transform.y);
             }
-            Direction::Down => {
-                movement = Vec2::new(-SCROLL_RATE as f64 * 2., 4.)
+            FlightPattern::Banking { direction, turn_y, turned } => {
+                let past_turn = match direction {
+                    Direction::Up => transform.y <= *turn_y,
+                    Direction::Down => transform.y >= *turn_y,
+                };
+                if past_turn && !*turned {
+                    *direction = direction.flipped();
+                    *turned = true;
+                    sprite.0.rotation = plane_rotation(Some(*direction));
+                }
+                movement = Vec2::new(-scroll_rate * 2., 4. * direction.sign());
+            }
+            FlightPattern::Heavy(direction) => {
+                movement = Vec2::new(-scroll_rate, 2. * direction.sign());
             }
         }
 
@@ -163,60 +320,135 @@ pub fn move_planes(mut transforms: ViewMut<Transform>, planes: View<Plane>) {
     }
 }
 
-pub fn grow_ground(transforms: View<Transform>, players: View<Player>, mut map: UniqueViewMut<HexMap>, mut points: UniqueViewMut<Points>) {
-    use crate::map::cube_round;
-    for (transform, _) in (&transforms, &players).iter() {
-        let mut pos = Vec2::new(transform.x as f32, transform.y as f32);
-        pos.y += 18. * 3.;
-        pos.x += 18. * 3.;
-
-        let adjacent = [
-            (0, 0),
-            (1, -1),
-            (1, 0),
-            (0, 1),
-            (-1, 1),
-            (-1, 0),
-            (0, -1),
-        ];
-
-        let (q, r) = map.pixel_to_hex_raw(pos / 2., 0.);
-        let (q, r, _) = cube_round(q, r, -r - q);        
-
-        for (q_mod, r_mod) in &adjacent {
-            let r = r + r_mod;
-            let q = q + q_mod;
-
-            if q >= WIDTH as i32 || q < 0 || r >= HEIGHT as i32 || r < 0 {
-                continue;
+/// Number of planes alive and how many have been culled so far, for debugging
+#[derive(Default)]
+pub struct PlaneStats {
+    pub live: usize,
+    pub culled: u64,
+}
+
+/// Deletes planes once their collider is fully past the left edge, or past the
+/// top/bottom edge they are flying towards
+pub fn cull_planes(mut all_storages: AllStoragesViewMut) {
+    let (off_screen, live) = all_storages.run(|planes: View<Plane>, transforms: View<Transform>, colliders: View<Collider>, sprites: View<Sprite>| {
+        let mut off_screen = Vec::new();
+        let mut live = 0;
+        for (id, (plane, transform, collider, sprite)) in (&planes, &transforms, &colliders, &sprites).iter().with_id() {
+            let bounds = collider.world_shape(transform, Some(sprite)).bounds();
+            let past_edge = match plane.heading() {
+                Some(Direction::Up) => bounds.max.y < 0.,
+                Some(Direction::Down) => bounds.min.y > WINDOW_HEIGHT as f32,
+                None => false,
+            };
+
+            if bounds.max.x < 0. || past_edge {
+                off_screen.push(id);
+            } else {
+                live += 1;
             }
+        }
+        (off_screen, live)
+    });
 
-            if let Some(tile) = map.tiles.get_mut((r * WIDTH as i32 + q) as usize) {
-                if tile.is_tilled && !tile.is_grown{
-                    tile.is_grown = true;
-                    points.0 += POINTS_GROW;
-                }
+    for &id in &off_screen {
+        all_storages.delete(id);
+    }
+
+    all_storages.run(|mut stats: UniqueViewMut<PlaneStats>| {
+        stats.live = live;
+        stats.culled += off_screen.len() as u64;
+    });
+}
+
+/// The point on the background map under the player's shadow
+fn player_map_pos(transform: &Transform) -> Vec2<f32> {
+    let mut pos = Vec2::new(transform.x as f32, transform.y as f32);
+    pos.y += 18. * 3.;
+    pos.x += 18. * 3.;
+    // The background is drawn at twice its size
+    pos / 2.
+}
+
+/// Grows every tilled tile within `radius` of `center` and hands them to `Scoring`
+fn grow_tiles(map: &mut HexMap, center: Axial, radius: i32, scoring: &mut Scoring) {
+    for hex in Cube::from(center).range(radius) {
+        let hex = Axial::from(hex);
+        if let Some(mut tile) = map.get_mut(hex) {
+            if tile.is_tilled && !tile.is_grown{
+                tile.is_grown = true;
+                scoring.grown_this_frame.push(hex);
             }
         }
     }
 }
 
-pub fn player_platform_check(player: View<Player>, transforms: View<Transform>, colliders: View<Collider>, mut heights: ViewMut<Height>) {
-    let (_, p_transform, p_collider, height) = (&player, &transforms, &colliders, &mut heights).iter().next().unwrap();
-    height.0 -= FALL_SPEED;
-    let (p_transform, p_collider) = ((*p_transform).clone(), (*p_collider).clone());
+pub fn grow_ground(powerups: UniqueView<ActivePowerUps>, transforms: View<Transform>, players: View<Player>, mut map: UniqueViewMut<HexMap>, mut scoring: UniqueViewMut<Scoring>) {
+    for (transform, _) in (&transforms, &players).iter() {
+        let hex = Axial::from(map.pixel_to_hex_raw(player_map_pos(transform), 0.).round());
+
+        grow_tiles(&mut map, hex, powerups.grow_radius(), &mut scoring);
+    }
+}
+
+/// Tracks the run's one bounce and how it ended
+#[derive(Default)]
+pub struct Landing {
+    pub bounced: bool,
+    pub death: Option<DeathCause>,
+    /// Set by `level_finish` once the player is past a level's finish line
+    pub finished: bool,
+}
+
+/// Once the player's height runs out this checks the tile they land on, tilled
+/// ground grows a patch and bounces them once, anything else ends the run
+pub fn player_landing(config: UniqueView<GameConfig>, player: View<Player>, transforms: View<Transform>, mut heights: ViewMut<Height>, mut map: UniqueViewMut<HexMap>, mut scoring: UniqueViewMut<Scoring>, mut powerups: UniqueViewMut<ActivePowerUps>, mut landing: UniqueViewMut<Landing>) {
+    let (_, transform, height) = (&player, &transforms, &mut heights).iter().next().unwrap();
+    if height.0 > 0. || landing.death.is_some() || landing.finished {
+        return;
+    }
+
+    let hex = map.pixel_to_hex(player_map_pos(transform));
+    let (is_wall, is_tilled) = match hex.and_then(|hex| map.get(hex)) {
+        Some(tile) => (tile.wall_height > tile.ground_height, tile.is_tilled),
+        None => (false, false),
+    };
 
-    for (transform, collider, _) in (&transforms, &colliders, !&player).iter() {
-        if Collider::intersects(collider, transform, &p_collider, &p_transform) {
-            height.0 = START_HEIGHT;
+    let cause = match hex {
+        _ if is_wall => DeathCause::Wall,
+        Some(hex) if is_tilled && !landing.bounced => {
+            grow_tiles(&mut map, hex, 1, &mut scoring);
+            landing.bounced = true;
+            height.0 = config.start_height * 0.5;
             return;
         }
+        _ if is_tilled => DeathCause::Tilled,
+        _ => DeathCause::Grass,
+    };
+
+    // The shield takes the hit and bounces the player like tilled ground would
+    if powerups.consume(PowerUpKind::Shield) {
+        height.0 = config.start_height * 0.5;
+    } else {
+        landing.death = Some(cause);
+    }
+}
+
+pub fn player_platform_check(config: UniqueView<GameConfig>, difficulty: UniqueView<Difficulty>, powerups: UniqueView<ActivePowerUps>, hash: UniqueView<SpatialHash>, player: View<Player>, planes: View<Plane>, transforms: View<Transform>, colliders: View<Collider>, sprites: View<Sprite>, mut heights: ViewMut<Height>, mut scoring: UniqueViewMut<Scoring>) {
+    let (_, p_transform, p_collider, p_sprite, height) = (&player, &transforms, &colliders, &sprites, &mut heights).iter().next().unwrap();
+    height.0 -= difficulty.fall_speed(&config) * powerups.fall_multiplier();
+    let p_shape = p_collider.world_shape(p_transform, Some(p_sprite));
+
+    // Pickups are in the hash too, only planes hold the player up
+    let hits = hash.query(&p_shape);
+    if hits.into_iter().any(|id| planes.contains(id)) {
+        height.0 = config.start_height;
+        scoring.touching_plane = true;
     }
 }
 
-pub fn player_height_visualiser(player: View<Player>, height: View<Height>, mut sprite: ViewMut<Sprite>) {
+pub fn player_height_visualiser(config: UniqueView<GameConfig>, player: View<Player>, height: View<Height>, mut sprite: ViewMut<Sprite>) {
     let (_, height, sprite) = (&player, &height, &mut sprite).iter().next().unwrap();
-    let mut percent = height.0 / START_HEIGHT;
+    let mut percent = height.0 / config.start_height;
     percent *= percent;
     let start = 1.;
     let end = 3.;
@@ -0,0 +1,271 @@
+use crate::{
+    consts::{
+        *,
+    },
+    hex::{
+        Axial,
+        Cube,
+    },
+    map::{
+        HexChunk,
+        HexTileData,
+    },
+    biomes::{
+        Biome,
+    },
+};
+
+use rand::{
+    Rng,
+    SeedableRng,
+};
+use rand_chacha::ChaCha8Rng;
+
+/// Fills in the tiles of a map chunk, `HexMap` streams chunks in through one of these
+pub trait MapGenerator {
+    /// Chunk `index` covers columns `index * CHUNK_WIDTH` onwards. The same
+    /// arguments must always give the same chunk
+    fn generate(&self, seed: u64, index: u64, height: usize, max_floor_height: u8) -> HexChunk;
+}
+
+/// Smooth noise in 0..1 interpolated between random values on a square lattice,
+/// the same seed and position always give the same value
+#[derive(Clone, Copy, Debug)]
+pub struct ValueNoise {
+    seed: u64,
+}
+
+impl ValueNoise {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+        }
+    }
+
+    fn lattice(&self, x: i32, y: i32) -> f32 {
+        // splitmix64 over the seed and lattice point
+        let mut z = self.seed
+            ^ (x as u32 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (y as u32 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let smooth = |t: f32| t * t * (3. - 2. * t);
+        let (tx, ty) = (smooth(x - x0), smooth(y - y0));
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let top = self.lattice(x0, y0) + (self.lattice(x0 + 1, y0) - self.lattice(x0, y0)) * tx;
+        let bottom = self.lattice(x0, y0 + 1) + (self.lattice(x0 + 1, y0 + 1) - self.lattice(x0, y0 + 1)) * tx;
+        top + (bottom - top) * ty
+    }
+
+    /// Several octaves of noise added together, still in 0..1
+    pub fn fractal(&self, x: f32, y: f32, octaves: u32) -> f32 {
+        let mut total = 0.;
+        let mut amplitude = 1.;
+        let mut frequency = 1.;
+        let mut max = 0.;
+        for octave in 0..octaves {
+            // Offset each octave so their lattice points don't line up
+            let offset = octave as f32 * 17.3;
+            total += self.sample(x * frequency + offset, y * frequency + offset) * amplitude;
+            max += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.;
+        }
+        total / max
+    }
+}
+
+/// Noise position of a hex so the noise isn't skewed by the axial layout
+fn noise_pos(hex: Axial) -> (f32, f32) {
+    (hex.q as f32 + hex.r as f32 * 0.5, hex.r as f32 * 0.866)
+}
+
+/// Tiles a chunk is made of before they are put into a `HexChunk`
+pub struct ChunkTiles {
+    pub start_column: i32,
+    pub height: usize,
+    pub tiles: Vec<HexTileData>,
+}
+
+impl ChunkTiles {
+    pub fn new(index: u64, height: usize) -> Self {
+        Self {
+            start_column: index as i32 * CHUNK_WIDTH as i32,
+            height,
+            tiles: (0..CHUNK_WIDTH * height).map(|_| HexTileData::new(0)).collect(),
+        }
+    }
+
+    /// Every hex in the chunk row by row
+    pub fn hexes(&self) -> impl Iterator<Item = Axial> {
+        let start = self.start_column;
+        let height = self.height as i32;
+        (0..height).flat_map(move |r| (0..CHUNK_WIDTH as i32).map(move |x| Axial::new(start + x, r)))
+    }
+
+    pub fn get_mut(&mut self, hex: Axial) -> Option<&mut HexTileData> {
+        let x = hex.q - self.start_column;
+        if x < 0 || x >= CHUNK_WIDTH as i32 || hex.r < 0 || hex.r >= self.height as i32 {
+            return None;
+        }
+        self.tiles.get_mut(hex.r as usize * CHUNK_WIDTH + x as usize)
+    }
+
+    /// Lays short tilled strips as dense as `density` asks for. Tilled tiles
+    /// never have walls so they are always safe to land on
+    pub fn till_strips(&mut self, rng: &mut ChaCha8Rng, density: StripDensity) {
+        let section_width = density.section_width.max(1);
+        for _ in 0..density.passes {
+            for section in 0..(CHUNK_WIDTH / section_width) {
+                // Sample as u32 so the stream doesn't depend on the width of usize
+                let row = rng.gen_range(0, self.height as u32 + 1) as usize;
+                let mut total = 0;
+                for _ in 0..5 {
+                    total += rng.gen_range(3, 7 + 1);
+                }
+                total /= 5;
+
+                if row >= self.height {
+                    continue;
+                }
+
+                for offset in 0..total {
+                    let column = section * section_width + offset;
+                    if column >= CHUNK_WIDTH {
+                        break;
+                    }
+                    if let Some(tile) = self.tiles.get_mut((row * CHUNK_WIDTH) + column) {
+                        tile.is_tilled = true;
+                        tile.wall_height = tile.ground_height;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Broken ring of stone wall around `center`, some of the ring is left out
+    pub fn place_ruin(&mut self, rng: &mut ChaCha8Rng, center: Axial, radius: i32) {
+        for hex in Cube::from(center).ring(radius) {
+            if rng.gen_range(0., 1.) < RUIN_GAP_CHANCE {
+                continue;
+            }
+            let wall = rng.gen_range(1, MAX_BRICK_HEIGHT as u32 + 1) as u8;
+            if let Some(tile) = self.get_mut(hex.into()) {
+                tile.wall_height = tile.wall_height.max((tile.ground_height + wall).min(MAX_BRICK_HEIGHT));
+            }
+        }
+    }
+
+    pub fn set_biome(&mut self, biome: Biome) {
+        for tile in self.tiles.iter_mut() {
+            tile.biome = biome;
+        }
+    }
+
+    pub fn into_chunk(self) -> HexChunk {
+        HexChunk {
+            tiles: self.tiles,
+        }
+    }
+}
+
+/// How many tilled strips a chunk gets, each pass lays at most one strip in
+/// every `section_width` columns
+#[derive(Clone, Copy, Debug)]
+pub struct StripDensity {
+    pub passes: u32,
+    pub section_width: usize,
+}
+
+/// Chance of each tile of a ruin's ring being left out
+const RUIN_GAP_CHANCE: f32 = 0.35;
+
+/// Rolling hills from fractal noise crossed by long winding stone walls, with
+/// the odd ruin and the usual tilled strips on top
+#[derive(Clone, Debug)]
+pub struct NoiseTerrain {
+    /// Hills per tile, smaller values give wider hills
+    pub hill_scale: f32,
+    /// How wide the band of noise that becomes walls is, 0 turns walls off
+    pub wall_width: f32,
+    /// Chance of a chunk having a ruin in it
+    pub ruin_chance: f32,
+    pub strips: StripDensity,
+}
+
+impl Default for NoiseTerrain {
+    fn default() -> Self {
+        Self {
+            hill_scale: 0.08,
+            wall_width: 0.015,
+            ruin_chance: 0.5,
+            strips: StripDensity { passes: 5, section_width: 10 },
+        }
+    }
+}
+
+impl NoiseTerrain {
+    /// Lays the hills and walls, shared with generators that build on this one
+    pub fn shape(&self, seed: u64, tiles: &mut ChunkTiles, max_floor_height: u8) {
+        let hills = ValueNoise::new(seed);
+        let walls = ValueNoise::new(seed.wrapping_add(1));
+
+        let hexes: Vec<Axial> = tiles.hexes().collect();
+        for hex in hexes {
+            let (x, y) = noise_pos(hex);
+            let hill = hills.fractal(x * self.hill_scale, y * self.hill_scale, 3);
+            let ground = ((hill * (max_floor_height as f32 + 1.)) as u8).min(max_floor_height);
+
+            // Walls follow where the wall noise crosses the middle, which gives long thin lines
+            let ridge = (walls.sample(x * 0.05, y * 0.05) - 0.5).abs();
+            let wall = if ridge < self.wall_width {
+                (ground + 1 + (hill * 3.) as u8).min(MAX_BRICK_HEIGHT).max(ground)
+            } else {
+                ground
+            };
+
+            if let Some(tile) = tiles.get_mut(hex) {
+                tile.ground_height = ground;
+                tile.wall_height = wall;
+            }
+        }
+    }
+}
+
+impl NoiseTerrain {
+    /// Generates a chunk's tiles without putting them in a `HexChunk` so biomes can touch them up
+    pub fn generate_tiles(&self, seed: u64, index: u64, height: usize, max_floor_height: u8) -> ChunkTiles {
+        // Each chunk gets its own stream of the map's rng so the terrain
+        // doesn't depend on the order or time chunks get streamed in
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(index);
+
+        let mut tiles = ChunkTiles::new(index, height);
+        self.shape(seed, &mut tiles, max_floor_height);
+
+        if rng.gen_range(0., 1.) < self.ruin_chance {
+            let center = Axial::new(
+                tiles.start_column + rng.gen_range(4, CHUNK_WIDTH as i32 - 4),
+                rng.gen_range(0, height as i32),
+            );
+            let radius = rng.gen_range(2, 4);
+            tiles.place_ruin(&mut rng, center, radius);
+        }
+
+        tiles.till_strips(&mut rng, self.strips);
+        tiles
+    }
+}
+
+impl MapGenerator for NoiseTerrain {
+    fn generate(&self, seed: u64, index: u64, height: usize, max_floor_height: u8) -> HexChunk {
+        self.generate_tiles(seed, index, height, max_floor_height).into_chunk()
+    }
+}